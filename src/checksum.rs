@@ -0,0 +1,161 @@
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+/// The hash functions that are used to verify transfers, in order of preference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashType {
+    Sha256,
+    Md5,
+}
+
+impl HashType {
+    /// Return the command that computes this hash on the remote host.
+    fn command(self) -> &'static str {
+        match self {
+            HashType::Sha256 => "sha256sum",
+            HashType::Md5 => "md5sum",
+        }
+    }
+}
+
+/// Hashes data with every `HashType` so that it can be compared with whichever hash the
+/// remote host is able to compute.
+#[derive(Clone, Default)]
+pub struct Checksum {
+    sha256: Sha256,
+    md5: Md5,
+}
+
+impl Checksum {
+    pub fn new() -> Self {
+        Checksum::default()
+    }
+
+    /// Add `data` to the hash.
+    pub fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        self.md5.update(data);
+    }
+
+    /// Add everything that is left in `reader` to the hash.
+    pub fn update_from_reader(&mut self, reader: &mut impl Read) -> io::Result<()> {
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let bytes_read = reader.read(&mut buffer)?;
+            if bytes_read == 0 {
+                return Ok(());
+            }
+            self.update(&buffer[..bytes_read]);
+        }
+    }
+
+    /// Return the hex digest of the data hashed so far with `hash_type`.
+    pub fn hex_digest(&self, hash_type: HashType) -> String {
+        let digest = match hash_type {
+            HashType::Sha256 => self.sha256.clone().finalize().to_vec(),
+            HashType::Md5 => self.md5.clone().finalize().to_vec(),
+        };
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// Compare `checksum` with the hash of the remote file `path`.
+///
+/// The remote hash is computed by running `sha256sum` or `md5sum` on the host. If neither is
+/// available, e.g., on SFTP-only servers, the remote file is read again over SFTP instead.
+pub fn verify_remote_checksum(
+    checksum: &Checksum,
+    path: &Path,
+    session: &ssh2::Session,
+    sftp: &ssh2::Sftp,
+) -> io::Result<()> {
+    let remote_digest = [HashType::Sha256, HashType::Md5]
+        .iter()
+        .find_map(|&hash_type| {
+            exec_remote_hash(hash_type, path, session)
+                .ok()
+                .map(|digest| (hash_type, digest))
+        });
+    let (hash_type, remote_digest) = match remote_digest {
+        Some(remote_digest) => remote_digest,
+        None => {
+            let mut remote_checksum = Checksum::new();
+            remote_checksum.update_from_reader(&mut sftp.open(path)?)?;
+            let hash_type = HashType::Sha256;
+            (hash_type, remote_checksum.hex_digest(hash_type))
+        }
+    };
+
+    let local_digest = checksum.hex_digest(hash_type);
+    if local_digest == remote_digest {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{:?} checksum mismatch, local {} but remote {}",
+                hash_type, local_digest, remote_digest
+            ),
+        ))
+    }
+}
+
+/// Return the hex digest of the remote file `path` that is computed by running a command on the host.
+fn exec_remote_hash(
+    hash_type: HashType,
+    path: &Path,
+    session: &ssh2::Session,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut channel = session.channel_session()?;
+    let path = path.to_str().ok_or("path is not valid UTF-8")?;
+    channel.exec(&format!("{} -- {}", hash_type.command(), shell_quote(path)))?;
+    let mut output = String::new();
+    channel.read_to_string(&mut output)?;
+    channel.wait_close()?;
+    let exit_status = channel.exit_status()?;
+    if exit_status != 0 {
+        return Err(Box::from(format!(
+            "channel closed with exit status {}",
+            exit_status
+        )));
+    }
+    let digest = output
+        .split_whitespace()
+        .next()
+        .ok_or("unexpected output")?
+        .to_lowercase();
+    if digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(digest)
+    } else {
+        Err(Box::from("unexpected output"))
+    }
+}
+
+/// Quote `s` so that it is a single word in a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        let mut checksum = Checksum::new();
+        checksum.update(b"hello ");
+        checksum.update_from_reader(&mut &b"world"[..]).unwrap();
+        assert_eq!(
+            checksum.hex_digest(HashType::Sha256),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(
+            checksum.hex_digest(HashType::Md5),
+            "5eb63bbbe01eeed093cb22bb8f5acdc3"
+        );
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
@@ -0,0 +1,272 @@
+use crate::file::FileEntry;
+use crate::utils::{bytes_to_string, time_to_string};
+
+use std::str::FromStr;
+
+/// The width that is always left for the file name when columns are dropped to fit a pane.
+const MIN_NAME_WIDTH: usize = 10;
+/// The width of the symbolic link target column, which is cut short if the target is longer.
+const LINK_WIDTH: usize = 20;
+
+/// A column of information about each entry that is shown after its name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Column {
+    Size,
+    /// The modification time in UTC.
+    Modified,
+    /// The file type and mode, e.g., `-rw-r--r--`.
+    Permissions,
+    /// The user and group ids of the owner, e.g., `1000:1000`.
+    Owner,
+    /// The target of a symbolic link.
+    Link,
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "size" => Ok(Column::Size),
+            "modified" | "mtime" => Ok(Column::Modified),
+            "permissions" | "perms" => Ok(Column::Permissions),
+            "owner" => Ok(Column::Owner),
+            "link" => Ok(Column::Link),
+            _ => Err(format!("invalid column \"{}\"", s)),
+        }
+    }
+}
+
+impl Column {
+    /// Parse a comma separated list of columns, e.g., `size,modified`.
+    pub fn parse_list(s: &str) -> Result<Vec<Column>, String> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+
+    /// Return the number of characters of this column.
+    fn width(self) -> usize {
+        match self {
+            Column::Size => 9,
+            Column::Modified => 16,
+            Column::Permissions => 10,
+            Column::Owner => 11,
+            Column::Link => LINK_WIDTH,
+        }
+    }
+
+    /// Return the text of this column for `entry`, padded to the width of the column.
+    fn format<E: FileEntry + ?Sized>(self, entry: &E) -> String {
+        let metadata = entry.metadata();
+        let text = match self {
+            Column::Size => entry.len().map(bytes_to_string),
+            Column::Modified => entry.modified().map(time_to_string),
+            Column::Permissions => metadata
+                .and_then(|metadata| metadata.permissions)
+                .map(permissions_to_string),
+            Column::Owner => metadata.and_then(|metadata| match (metadata.uid, metadata.gid) {
+                (Some(uid), Some(gid)) => Some(format!("{}:{}", uid, gid)),
+                _ => None,
+            }),
+            Column::Link => metadata
+                .and_then(|metadata| metadata.link_target.as_ref())
+                .map(|target| {
+                    let target = format!("→ {}", target.to_string_lossy());
+                    if target.chars().count() > LINK_WIDTH {
+                        let cut: String = target.chars().take(LINK_WIDTH - 1).collect();
+                        format!("{}…", cut)
+                    } else {
+                        target
+                    }
+                }),
+        };
+        let text = text.unwrap_or_default();
+        if self == Column::Size {
+            format!("{:>width$}", text, width = self.width())
+        } else {
+            format!("{:width$}", text, width = self.width())
+        }
+    }
+}
+
+/// Return the first of `columns` that fit in `width` while leaving room for the file name.
+pub fn fit_columns(columns: &[Column], width: usize) -> &[Column] {
+    let mut used = MIN_NAME_WIDTH;
+    let count = columns
+        .iter()
+        .take_while(|column| {
+            used += column.width() + 1;
+            used <= width
+        })
+        .count();
+    &columns[..count]
+}
+
+/// Return the name of `entry` followed by `columns`, in `width` characters.
+///
+/// Directories end with a `/`. The columns are padded to line up with the other entries, unless
+/// the name does not fit then. In that case the padding is left out, and if the name still does
+/// not fit, the last columns are dropped until `MIN_NAME_WIDTH` is left for it. Only then is the
+/// name cut short, ending with a `…`.
+pub fn format_entry<E: FileEntry + ?Sized>(entry: &E, columns: &[Column], width: usize) -> String {
+    let mut name = entry.file_name_lossy().unwrap_or_default().into_owned();
+    if entry.is_dir() {
+        name.push('/');
+    }
+    let name_len = name.chars().count();
+    let mut texts: Vec<_> = columns.iter().map(|column| column.format(entry)).collect();
+    let texts_width =
+        |texts: &[String]| -> usize { texts.iter().map(|text| text.chars().count() + 1).sum() };
+    if name_len + texts_width(&texts) > width {
+        texts = texts.iter().map(|text| text.trim().to_string()).collect();
+        while !texts.is_empty()
+            && name_len + texts_width(&texts) > width
+            && width.saturating_sub(texts_width(&texts)) < MIN_NAME_WIDTH
+        {
+            texts.pop();
+        }
+    }
+
+    let name_width = width.saturating_sub(texts_width(&texts));
+    let mut text = format!("{:width$}", cut_name(name, name_width), width = name_width);
+    for column_text in texts {
+        text.push(' ');
+        text.push_str(&column_text);
+    }
+    text
+}
+
+/// Return `name` cut short to `width` characters, ending with a `…` if anything was cut.
+fn cut_name(name: String, width: usize) -> String {
+    if name.chars().count() <= width {
+        name
+    } else if width == 0 {
+        String::new()
+    } else {
+        let mut cut: String = name.chars().take(width - 1).collect();
+        cut.push('…');
+        cut
+    }
+}
+
+/// Return a file mode as `ls -l` shows it, e.g., `drwxr-x---`.
+pub fn permissions_to_string(mode: u32) -> String {
+    let file_type = match mode & 0o170_000 {
+        0o040_000 => 'd',
+        0o120_000 => 'l',
+        0o020_000 => 'c',
+        0o060_000 => 'b',
+        0o010_000 => 'p',
+        0o140_000 => 's',
+        _ => '-',
+    };
+    let mut text = String::with_capacity(10);
+    text.push(file_type);
+    for (i, &c) in ['r', 'w', 'x', 'r', 'w', 'x', 'r', 'w', 'x']
+        .iter()
+        .enumerate()
+    {
+        let bit = 0o400 >> i;
+        // The setuid, setgid and sticky bits replace the execute bit of the user, group and
+        // others, in lower case if that is also set.
+        let special = match i {
+            2 => Some((0o4000, 's')),
+            5 => Some((0o2000, 's')),
+            8 => Some((0o1000, 't')),
+            _ => None,
+        };
+        text.push(match special {
+            Some((special_bit, special)) if mode & special_bit != 0 => {
+                if mode & bit != 0 {
+                    special
+                } else {
+                    special.to_ascii_uppercase()
+                }
+            }
+            _ if mode & bit != 0 => c,
+            _ => '-',
+        });
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::{FileMetadata, LocalFileEntry};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_columns() {
+        assert_eq!(permissions_to_string(0o100_644), "-rw-r--r--");
+        assert_eq!(permissions_to_string(0o040_750), "drwxr-x---");
+        assert_eq!(permissions_to_string(0o041_777), "drwxrwxrwt");
+        assert_eq!(permissions_to_string(0o104_644), "-rwSr--r--");
+        assert_eq!(permissions_to_string(0o120_777), "lrwxrwxrwx");
+
+        assert_eq!(
+            Column::parse_list("size, modified,perms"),
+            Ok(vec![Column::Size, Column::Modified, Column::Permissions])
+        );
+        assert_eq!(Column::parse_list(""), Ok(vec![]));
+        assert!(Column::parse_list("size,colour").is_err());
+
+        let columns = [Column::Size, Column::Modified, Column::Owner];
+        assert_eq!(fit_columns(&columns, 80), &columns);
+        assert_eq!(fit_columns(&columns, 40), &columns[..2]);
+        assert_eq!(fit_columns(&columns, 20), &columns[..1]);
+        assert_eq!(fit_columns(&columns, 19), &[]);
+
+        let entry = LocalFileEntry::File(
+            PathBuf::from("/home/notes.txt"),
+            1_500,
+            FileMetadata {
+                modified: Some(1_589_724_180),
+                permissions: Some(0o120_777),
+                uid: Some(1000),
+                gid: Some(100),
+                link_target: Some(PathBuf::from("/srv/shared/notes/2020/notes.txt")),
+            },
+        );
+        assert_eq!(
+            format_entry(&entry, &[Column::Size, Column::Modified], 40),
+            "notes.txt        1.5 KB 2020-05-17 14:03"
+        );
+        assert_eq!(
+            format_entry(&entry, &[Column::Permissions, Column::Owner], 30),
+            "notes.txt  lrwxrwxrwx 1000:100"
+        );
+        assert_eq!(
+            format_entry(&entry, &[Column::Link], 30),
+            "notes.txt → /srv/shared/notes…"
+        );
+        assert_eq!(
+            format_entry(&entry, &[Column::Size, Column::Modified], 20),
+            "notes.txt     1.5 KB"
+        );
+        assert_eq!(format_entry(&entry, &[Column::Size], 8), "notes.t…");
+
+        let entry = LocalFileEntry::File(
+            PathBuf::from("/home/meeting-notes.txt"),
+            1_500,
+            FileMetadata::default(),
+        );
+        assert_eq!(
+            format_entry(&entry, &[Column::Size], 25),
+            "meeting-notes.txt  1.5 KB"
+        );
+        assert_eq!(
+            format_entry(&entry, &[Column::Size], 21),
+            "meeting-notes… 1.5 KB"
+        );
+
+        let entry = LocalFileEntry::Directory(PathBuf::from("/home/src"), FileMetadata::default());
+        assert_eq!(
+            format_entry(&entry, &[Column::Size, Column::Modified], 40),
+            "src/                                    "
+        );
+    }
+}
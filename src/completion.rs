@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+
+/// What pressing Tab does to a path that is being typed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Completion {
+    /// The path was completed, or completed as far as all of the candidates agree.
+    Text(String),
+    /// The candidates agree no further than what was typed.
+    Ambiguous(Vec<String>),
+    /// Nothing starts with what was typed.
+    None,
+}
+
+/// Return the path that `text` refers to, where `~` is `home_dir` and a relative path is
+/// relative to `working_dir`.
+///
+/// Returns `None` if `text` starts with `~` and there is no home directory.
+pub fn expand_path(text: &str, working_dir: &Path, home_dir: Option<&Path>) -> Option<PathBuf> {
+    let path = if text == "~" {
+        home_dir?.to_path_buf()
+    } else if let Some(rest) = text.strip_prefix("~/") {
+        home_dir?.join(rest)
+    } else {
+        working_dir.join(text)
+    };
+    Some(path)
+}
+
+/// Split `text` into the directory to complete in, as typed, and the start of the name to
+/// complete, e.g., `src/ma` into `src/` and `ma`.
+pub fn split_path(text: &str) -> (&str, &str) {
+    match text.rfind('/') {
+        Some(i) => text.split_at(i + 1),
+        None => ("", text),
+    }
+}
+
+/// Complete the directory name at the end of `text` with the `names` of the directories in the
+/// directory that it is in.
+///
+/// A unique match ends with a `/`, so that Tab can be pressed again to continue in it. Hidden
+/// directories are only completed if the name being typed starts with a `.`.
+pub fn complete(text: &str, names: &[String]) -> Completion {
+    if text == "~" {
+        return Completion::Text(String::from("~/"));
+    }
+    let (dir, prefix) = split_path(text);
+    let mut candidates: Vec<&String> = names
+        .iter()
+        .filter(|name| {
+            name.starts_with(prefix) && (prefix.starts_with('.') || !name.starts_with('.'))
+        })
+        .collect();
+    candidates.sort();
+    match candidates.as_slice() {
+        [] => Completion::None,
+        [name] => Completion::Text(format!("{}{}/", dir, name)),
+        [first, rest @ ..] => {
+            let common_len = rest.iter().fold(first.len(), |len, name| {
+                first[..len]
+                    .chars()
+                    .zip(name.chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a.len_utf8())
+                    .sum()
+            });
+            if common_len > prefix.len() {
+                Completion::Text(format!("{}{}", dir, &first[..common_len]))
+            } else {
+                Completion::Ambiguous(candidates.into_iter().cloned().collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion() {
+        let home = Path::new("/home/me");
+        let cwd = Path::new("/srv");
+        assert_eq!(
+            expand_path("~", cwd, Some(home)),
+            Some(PathBuf::from("/home/me"))
+        );
+        assert_eq!(
+            expand_path("~/logs", cwd, Some(home)),
+            Some(PathBuf::from("/home/me/logs"))
+        );
+        assert_eq!(expand_path("~/logs", cwd, None), None);
+        assert_eq!(
+            expand_path("www/../data", cwd, None),
+            Some(PathBuf::from("/srv/www/../data"))
+        );
+        assert_eq!(expand_path("/etc", cwd, None), Some(PathBuf::from("/etc")));
+        assert_eq!(split_path("src/ma"), ("src/", "ma"));
+        assert_eq!(split_path("/"), ("/", ""));
+        assert_eq!(split_path("ma"), ("", "ma"));
+        assert_eq!(split_path("~/"), ("~/", ""));
+
+        let names: Vec<String> = ["backups", "bin", "build", ".cache", "logs"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        assert_eq!(
+            complete("~/lo", &names),
+            Completion::Text(String::from("~/logs/"))
+        );
+        assert_eq!(
+            complete("/var/bu", &names),
+            Completion::Text(String::from("/var/build/"))
+        );
+        assert_eq!(
+            complete("b", &names),
+            Completion::Ambiguous(vec![
+                String::from("backups"),
+                String::from("bin"),
+                String::from("build"),
+            ])
+        );
+        assert_eq!(
+            complete("ba", &names),
+            Completion::Text(String::from("backups/"))
+        );
+        assert_eq!(complete("x", &names), Completion::None);
+        assert_eq!(complete("~", &names), Completion::Text(String::from("~/")));
+        assert_eq!(
+            complete("~/", &names),
+            Completion::Ambiguous(vec![
+                String::from("backups"),
+                String::from("bin"),
+                String::from("build"),
+                String::from("logs"),
+            ])
+        );
+        assert_eq!(
+            complete(".c", &names),
+            Completion::Text(String::from(".cache/"))
+        );
+
+        let names = vec![String::from("photos-2019"), String::from("photos-2020")];
+        assert_eq!(
+            complete("ph", &names),
+            Completion::Text(String::from("photos-20"))
+        );
+    }
+}
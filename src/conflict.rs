@@ -0,0 +1,365 @@
+use crate::file::FileEntry;
+use crate::queue::Transfer;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+use std::fs::{metadata, symlink_metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+use tui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    widgets::{Block, Borders, Clear, List, ListState, Text},
+};
+
+const DIALOG_HIGHLIGHT_COLOR: Color = Color::LightMagenta;
+
+/// What to do with a transfer whose destination already exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Ask the user every time.
+    Ask,
+    Overwrite,
+    Skip,
+    /// Transfer to a new name with a numbered suffix.
+    Rename,
+    /// Continue a previous transfer to the destination.
+    Resume,
+    /// Overwrite the destination only if the source was modified more recently.
+    OverwriteIfNewer,
+}
+
+/// The choices of the conflict dialog.
+const CHOICES: [ConflictPolicy; 5] = [
+    ConflictPolicy::Overwrite,
+    ConflictPolicy::Skip,
+    ConflictPolicy::Rename,
+    ConflictPolicy::Resume,
+    ConflictPolicy::OverwriteIfNewer,
+];
+
+impl ConflictPolicy {
+    /// Return the key that selects this policy in the conflict dialog.
+    fn get_key(self) -> char {
+        match self {
+            ConflictPolicy::Ask => unreachable!(),
+            ConflictPolicy::Overwrite => 'o',
+            ConflictPolicy::Skip => 's',
+            ConflictPolicy::Rename => 'n',
+            ConflictPolicy::Resume => 'r',
+            ConflictPolicy::OverwriteIfNewer => 'u',
+        }
+    }
+
+    fn get_description(self) -> &'static str {
+        match self {
+            ConflictPolicy::Ask => "Ask",
+            ConflictPolicy::Overwrite => "Overwrite",
+            ConflictPolicy::Skip => "Skip",
+            ConflictPolicy::Rename => "Rename with a suffix",
+            ConflictPolicy::Resume => "Resume",
+            ConflictPolicy::OverwriteIfNewer => "Overwrite if newer",
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ask" => Ok(ConflictPolicy::Ask),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "rename" => Ok(ConflictPolicy::Rename),
+            "resume" => Ok(ConflictPolicy::Resume),
+            "newer" => Ok(ConflictPolicy::OverwriteIfNewer),
+            _ => Err(format!("unknown conflict policy \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.get_description())
+    }
+}
+
+/// A dialog that asks the user what to do with a transfer whose destination already exists.
+pub struct ConflictDialog {
+    transfer: Transfer,
+    selected: usize,
+    apply_to_all: bool,
+}
+
+impl ConflictDialog {
+    pub fn new(transfer: Transfer) -> Self {
+        ConflictDialog {
+            transfer,
+            selected: 0,
+            apply_to_all: false,
+        }
+    }
+
+    /// Handle a key press.
+    ///
+    /// Once the user has made a choice, return the transfer, the chosen policy, and whether
+    /// the policy should be applied to all future conflicts.
+    pub fn on_event(&mut self, key: KeyEvent) -> Option<(Transfer, ConflictPolicy, bool)> {
+        if key.modifiers != KeyModifiers::NONE {
+            return None;
+        }
+        let policy = match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = (self.selected + 1) % CHOICES.len();
+                None
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.selected = (self.selected + CHOICES.len() - 1) % CHOICES.len();
+                None
+            }
+            KeyCode::Char('a') => {
+                self.apply_to_all = !self.apply_to_all;
+                None
+            }
+            KeyCode::Enter => Some(CHOICES[self.selected]),
+            KeyCode::Esc => Some(ConflictPolicy::Skip),
+            KeyCode::Char(c) => CHOICES.iter().find(|p| p.get_key() == c).copied(),
+            _ => None,
+        };
+        policy.map(|policy| (self.transfer.clone(), policy, self.apply_to_all))
+    }
+
+    /// Draw this dialog in the middle of `rect`.
+    pub fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>, rect: Rect)
+    where
+        B: tui::backend::Backend,
+    {
+        let height = CHOICES.len() as u16 + 5;
+        let width = 50.min(rect.width);
+        let rect = Rect::new(
+            rect.x + (rect.width - width) / 2,
+            rect.y + rect.height.saturating_sub(height) / 2,
+            width,
+            height.min(rect.height),
+        );
+        frame.render_widget(Clear, rect);
+        let block = Block::default()
+            .title("File already exists")
+            .borders(Borders::ALL);
+        frame.render_widget(block, rect);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints(
+                [
+                    Constraint::Length(2),
+                    Constraint::Length(CHOICES.len() as u16),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
+            .split(rect);
+
+        let message = format!("\"{}\" already exists.", self.transfer.get_dest().display());
+        let message = List::new(vec![Text::raw(message)].into_iter());
+        frame.render_widget(message, chunks[0]);
+
+        let items: Vec<_> = CHOICES
+            .iter()
+            .map(|policy| Text::raw(format!("[{}] {}", policy.get_key(), policy)))
+            .collect();
+        let choices = List::new(items.into_iter())
+            .highlight_style(Style::default().bg(DIALOG_HIGHLIGHT_COLOR))
+            .highlight_symbol(">>");
+        let mut state = ListState::default();
+        state.select(Some(self.selected));
+        frame.render_stateful_widget(choices, chunks[1], &mut state);
+
+        let apply_to_all = format!(
+            "[a] Apply to all conflicts: {}",
+            if self.apply_to_all { "yes" } else { "no" }
+        );
+        let apply_to_all = List::new(vec![Text::raw(apply_to_all)].into_iter());
+        frame.render_widget(apply_to_all, chunks[2]);
+    }
+}
+
+/// Return `dest` with the first numbered suffix, e.g., "notes-1.txt", for which `exists` is `false`.
+///
+/// The suffix goes before the first dot of the name, so "logs.tar.gz" becomes "logs-1.tar.gz".
+/// The dot that starts the name of a hidden file does not count. If `exists` fails, so does this.
+pub fn get_unused_path(
+    dest: &Path,
+    exists: impl Fn(&Path) -> io::Result<bool>,
+) -> io::Result<PathBuf> {
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let (stem, extension) = match name.char_indices().skip(1).find(|&(_, c)| c == '.') {
+        Some((i, _)) => name.split_at(i),
+        None => (name.as_ref(), ""),
+    };
+    for i in 1.. {
+        let path = dest.with_file_name(format!("{}-{}{}", stem, i, extension));
+        if !exists(&path)? {
+            return Ok(path);
+        }
+    }
+    unreachable!()
+}
+
+/// Return `true` if something is at `path` on the side that `transfer` writes to.
+///
+/// This asks the file system rather than the file lists, which leave out hidden files and
+/// anything created since they were last fetched. Only a missing `path` counts as `false`,
+/// any other error, e.g., a lost connection, is returned.
+pub fn dest_exists(transfer: &Transfer, path: &Path, sftp: &ssh2::Sftp) -> io::Result<bool> {
+    match transfer {
+        Transfer::Upload(..) => remote_path_exists(path, sftp),
+        Transfer::Download(..) => local_path_exists(path),
+    }
+}
+
+/// Return `true` if something is at the local `path`, without following symbolic links.
+fn local_path_exists(path: &Path) -> io::Result<bool> {
+    match symlink_metadata(path) {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Return `true` if something is at the remote `path`.
+///
+/// libssh2 does not tell why the server failed to stat `path`, so the parent directory is
+/// listed to tell a missing `path` apart from, e.g., one we lack the permissions for.
+fn remote_path_exists(path: &Path, sftp: &ssh2::Sftp) -> io::Result<bool> {
+    if sftp.lstat(path).is_ok() {
+        return Ok(true);
+    }
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let entries = sftp.readdir(parent)?;
+    Ok(entries
+        .iter()
+        .any(|(entry, _)| entry.file_name() == path.file_name()))
+}
+
+/// Return `true` if the source of `transfer` was modified after its destination.
+pub fn is_source_newer(transfer: &Transfer, sftp: &ssh2::Sftp) -> io::Result<bool> {
+    let local_mtime = |path| -> io::Result<u64> {
+        let modified = metadata(path)?.modified()?;
+        Ok(modified
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_secs())
+            .unwrap_or(0))
+    };
+    let remote_mtime = |path| -> io::Result<u64> { Ok(sftp.stat(path)?.mtime.unwrap_or(0)) };
+    let (source_mtime, dest_mtime) = match transfer {
+        Transfer::Upload(source, dest) => (local_mtime(source.path())?, remote_mtime(dest)?),
+        Transfer::Download(source, dest) => (remote_mtime(source.path())?, local_mtime(dest)?),
+    };
+    Ok(source_mtime > dest_mtime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::{FileMetadata, RemoteFileEntry};
+    use crate::utils::buffer_without_style;
+    use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+    #[test]
+    fn test_conflict_dialog() {
+        let transfer = Transfer::Download(
+            RemoteFileEntry::File(
+                PathBuf::from("home/notes.txt"),
+                100,
+                FileMetadata::default(),
+            ),
+            PathBuf::from("/tmp/notes.txt"),
+        );
+        let mut dialog = ConflictDialog::new(transfer);
+        assert!(dialog.on_event(KeyCode::Down.into()).is_none());
+        assert!(dialog.on_event(KeyCode::Char('a').into()).is_none());
+
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal
+            .draw(|mut frame| {
+                let rect = frame.size();
+                dialog.draw(&mut frame, rect);
+            })
+            .unwrap();
+        assert_eq!(
+            buffer_without_style(terminal.backend().buffer()),
+            Buffer::with_lines(vec![
+                "                                        ",
+                "┌File already exists───────────────────┐",
+                "│\"/tmp/notes.txt\" already exists.      │",
+                "│                                      │",
+                "│  [o] Overwrite                       │",
+                "│>>[s] Skip                            │",
+                "│  [n] Rename with a suffix            │",
+                "│  [r] Resume                          │",
+                "│  [u] Overwrite if newer              │",
+                "│[a] Apply to all conflicts: yes       │",
+                "└──────────────────────────────────────┘",
+                "                                        ",
+            ])
+        );
+
+        let (_, policy, apply_to_all) = dialog.on_event(KeyCode::Char('r').into()).unwrap();
+        assert_eq!(policy, ConflictPolicy::Resume);
+        assert!(apply_to_all);
+        let (_, policy, _) = dialog.on_event(KeyCode::Enter.into()).unwrap();
+        assert_eq!(policy, ConflictPolicy::Skip);
+        assert_eq!(
+            "newer".parse::<ConflictPolicy>(),
+            Ok(ConflictPolicy::OverwriteIfNewer)
+        );
+    }
+
+    #[test]
+    fn test_get_unused_path() {
+        let existing = [
+            PathBuf::from("/a/notes.txt"),
+            PathBuf::from("/a/notes-1.txt"),
+        ];
+        let exists = |path: &Path| Ok(existing.iter().any(|p| p == path));
+        assert_eq!(
+            get_unused_path(Path::new("/a/notes.txt"), exists).unwrap(),
+            PathBuf::from("/a/notes-2.txt")
+        );
+        assert_eq!(
+            get_unused_path(Path::new("/a/build"), exists).unwrap(),
+            PathBuf::from("/a/build-1")
+        );
+        assert_eq!(
+            get_unused_path(Path::new("/a/logs.tar.gz"), exists).unwrap(),
+            PathBuf::from("/a/logs-1.tar.gz")
+        );
+        assert_eq!(
+            get_unused_path(Path::new("/a/.config.json"), exists).unwrap(),
+            PathBuf::from("/a/.config-1.json")
+        );
+        assert_eq!(
+            get_unused_path(Path::new("/a/.bashrc"), exists).unwrap(),
+            PathBuf::from("/a/.bashrc-1")
+        );
+        let fails = |_: &Path| Err(io::Error::other("connection lost"));
+        assert!(get_unused_path(Path::new("/a/notes.txt"), fails).is_err());
+    }
+
+    #[test]
+    fn test_local_path_exists() {
+        let dir = std::env::temp_dir().join(format!("rftp-conflict-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "notes").unwrap();
+        assert!(local_path_exists(&dir.join("notes.txt")).unwrap());
+        assert!(!local_path_exists(&dir.join("todo.txt")).unwrap());
+        // A file cannot contain anything, which is an error rather than a missing path.
+        assert!(local_path_exists(&dir.join("notes.txt/todo.txt")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The host to connect to as it is given on the command line, e.g.,
+/// `sftp://user@host:2222/dir`, `user@host:/dir`, or `[::1]:2222`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Destination {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+    /// The remote directory to start in instead of the home directory.
+    pub path: Option<PathBuf>,
+}
+
+impl FromStr for Destination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid_destination = || format!("invalid destination \"{}\"", s);
+        let url = ["sftp://", "ssh://"]
+            .iter()
+            .find_map(|scheme| s.strip_prefix(scheme));
+        let destination = if let Some(url) = url {
+            parse_url(url)
+        } else {
+            parse_scp_style(s)
+        };
+        let destination = destination.ok_or_else(invalid_destination)?;
+        if destination.host.is_empty() || destination.user.as_deref() == Some("") {
+            return Err(invalid_destination());
+        }
+        Ok(destination)
+    }
+}
+
+/// Parse the rest of an `sftp://[user@]host[:port][/path]` URL.
+///
+/// As in the SFTP URI draft, a path that starts with `/~/` is relative to the home directory.
+fn parse_url(url: &str) -> Option<Destination> {
+    let (authority, path) = match url.find('/') {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+    let (user, host_port) = split_user(authority);
+    // Connection parameters after a `;`, e.g., a fingerprint, are not supported.
+    let user = match user {
+        Some(user) => Some(percent_decode(user.split(';').next().unwrap_or(user))?),
+        None => None,
+    };
+    let (host, port) = split_host_port(host_port)?;
+    let port = match port {
+        Some(port) if !port.is_empty() => Some(port.parse().ok()?),
+        _ => None,
+    };
+    let path = percent_decode(path)?;
+    let path = match path.as_str() {
+        "" | "/~" | "/~/" => None,
+        path => Some(PathBuf::from(path.strip_prefix("/~/").unwrap_or(path))),
+    };
+    Some(Destination {
+        user,
+        host: host.to_string(),
+        port,
+        path,
+    })
+}
+
+/// Parse `[user@]host[:port]` or `[user@]host:path`, where IPv6 addresses with a port or path
+/// are in brackets.
+///
+/// Like scp, a path that does not start with `/` is relative to the home directory. A number
+/// after the colon is a port, so a directory with a numeric name must be written as, e.g.,
+/// `host:./2020`.
+fn parse_scp_style(destination: &str) -> Option<Destination> {
+    let (user, host_rest) = split_user(destination);
+    let (host, rest) = split_host_port(host_rest)?;
+    let (port, path) = match rest {
+        Some(rest) if !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit()) => {
+            (Some(rest.parse().ok()?), None)
+        }
+        Some(rest) => (None, Some(rest)),
+        None => (None, None),
+    };
+    let path = match path {
+        None | Some("") | Some("~") | Some("~/") => None,
+        Some(path) => Some(PathBuf::from(path.strip_prefix("~/").unwrap_or(path))),
+    };
+    Some(Destination {
+        user: user.map(str::to_string),
+        host: host.to_string(),
+        port,
+        path,
+    })
+}
+
+/// Split `user@rest` at the last `@`, since user names may contain one.
+fn split_user(s: &str) -> (Option<&str>, &str) {
+    match s.rfind('@') {
+        Some(i) => (Some(&s[..i]), &s[i + 1..]),
+        None => (None, s),
+    }
+}
+
+/// Split `host[:rest]` or `[host][:rest]` into the host and what follows the colon.
+///
+/// An IPv6 address without brackets, i.e., with more than one colon, is all host.
+fn split_host_port(s: &str) -> Option<(&str, Option<&str>)> {
+    if let Some(s) = s.strip_prefix('[') {
+        let end = s.find(']')?;
+        let rest = match &s[end + 1..] {
+            "" => None,
+            rest => Some(rest.strip_prefix(':')?),
+        };
+        Some((&s[..end], rest))
+    } else if s.matches(':').count() > 1 {
+        Some((s, None))
+    } else {
+        match s.find(':') {
+            Some(i) => Some((&s[..i], Some(&s[i + 1..]))),
+            None => Some((s, None)),
+        }
+    }
+}
+
+/// Decode the `%XX` escapes of a URL component, or return `None` if they are not valid UTF-8.
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn destination(
+        user: Option<&str>,
+        host: &str,
+        port: Option<u16>,
+        path: Option<&str>,
+    ) -> Destination {
+        Destination {
+            user: user.map(str::to_string),
+            host: host.to_string(),
+            port,
+            path: path.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn test_destination() {
+        let parse = |s: &str| s.parse::<Destination>();
+        assert_eq!(
+            parse("example.com"),
+            Ok(destination(None, "example.com", None, None))
+        );
+        assert_eq!(
+            parse("deploy@example.com"),
+            Ok(destination(Some("deploy"), "example.com", None, None))
+        );
+        assert_eq!(
+            parse("deploy@example.com:/srv/www"),
+            Ok(destination(
+                Some("deploy"),
+                "example.com",
+                None,
+                Some("/srv/www")
+            ))
+        );
+        assert_eq!(
+            parse("example.com:~/logs"),
+            Ok(destination(None, "example.com", None, Some("logs")))
+        );
+        assert_eq!(
+            parse("example.com:2222"),
+            Ok(destination(None, "example.com", Some(2222), None))
+        );
+        assert_eq!(
+            parse("[::1]:2222"),
+            Ok(destination(None, "::1", Some(2222), None))
+        );
+        assert_eq!(
+            parse("me@[fe80::1]:/tmp"),
+            Ok(destination(Some("me"), "fe80::1", None, Some("/tmp")))
+        );
+        assert_eq!(parse("::1"), Ok(destination(None, "::1", None, None)));
+        assert_eq!(
+            parse("sftp://j%40doe@example.com:2222/srv/my%20files"),
+            Ok(destination(
+                Some("j@doe"),
+                "example.com",
+                Some(2222),
+                Some("/srv/my files")
+            ))
+        );
+        assert_eq!(
+            parse("sftp://example.com/~/logs"),
+            Ok(destination(None, "example.com", None, Some("logs")))
+        );
+        assert_eq!(
+            parse("ssh://user;fingerprint=abc@[::1]"),
+            Ok(destination(Some("user"), "::1", None, None))
+        );
+        assert!(parse("sftp://example.com:ssh/").is_err());
+        assert!(parse("@example.com").is_err());
+        assert!(parse("[::1").is_err());
+        assert!(parse("example.com:99999").is_err());
+    }
+}
@@ -1,14 +1,27 @@
+use crate::checksum::{verify_remote_checksum, Checksum};
+use crate::column::{fit_columns, format_entry, Column};
+use crate::filter::Filter;
 use crate::progress::Progress;
-use crate::utils::{bytes_to_string, get_remote_home_dir};
+use crate::rate_limit::RateLimiter;
+use crate::utils::get_remote_home_dir;
 
-use ssh2;
+use crossbeam_channel::bounded;
+use filetime::{set_file_times, FileTime};
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::env;
 use std::error::Error;
-use std::fs::{canonicalize, metadata, read_dir, File};
+use std::fmt;
+use std::fs::{
+    canonicalize, create_dir_all, metadata, read_dir, read_link, remove_file, rename,
+    symlink_metadata, File, Metadata, OpenOptions,
+};
 use std::io;
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::mem::{replace, take};
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 use tui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Style},
@@ -19,26 +32,87 @@ const FILELIST_FILE_COLOR: Color = Color::Green;
 const FILELIST_DIRECTORY_COLOR: Color = Color::Blue;
 const FILELIST_HIGHLIGHT_COLOR: Color = Color::LightMagenta;
 
-#[derive(Clone, PartialEq, Eq, Ord)]
+/// A local file with its size, a directory, or the parent directory.
+#[derive(Clone, PartialEq, Eq)]
 pub enum LocalFileEntry {
-    File(PathBuf, u64),
-    Directory(PathBuf),
+    File(PathBuf, u64, FileMetadata),
+    Directory(PathBuf, FileMetadata),
     Parent(PathBuf),
 }
 
-#[derive(Clone, PartialEq, Eq, Ord)]
+/// A remote file with its size, a directory, or the parent directory.
+#[derive(Clone, PartialEq, Eq)]
 pub enum RemoteFileEntry {
-    File(PathBuf, u64),
-    Directory(PathBuf),
+    File(PathBuf, u64, FileMetadata),
+    Directory(PathBuf, FileMetadata),
     Parent(PathBuf),
 }
 
+/// What is known about a file or directory besides its size, for sorting and showing it.
+///
+/// A symbolic link is listed as the file or directory that it points to, but with the
+/// metadata of the link itself, like `ls -l` does.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// The modification time in seconds since the Unix epoch.
+    pub modified: Option<u64>,
+    /// The file type and mode bits, as in `st_mode`.
+    pub permissions: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// The target of a symbolic link.
+    pub link_target: Option<PathBuf>,
+}
+
+impl FileMetadata {
+    /// Return the metadata of a local file from its `symlink_metadata()`.
+    fn from_local(metadata: &Metadata, link_target: Option<PathBuf>) -> Self {
+        let modified = FileTime::from_last_modification_time(metadata).unix_seconds();
+        #[cfg(unix)]
+        let (permissions, uid, gid) = {
+            use std::os::unix::fs::MetadataExt;
+            (
+                Some(metadata.mode()),
+                Some(metadata.uid()),
+                Some(metadata.gid()),
+            )
+        };
+        #[cfg(not(unix))]
+        let (permissions, uid, gid) = (None, None, None);
+        FileMetadata {
+            modified: Some(modified.max(0) as u64),
+            permissions,
+            uid,
+            gid,
+            link_target,
+        }
+    }
+
+    /// Return the metadata of a remote file from its `lstat()`.
+    fn from_remote(stat: &ssh2::FileStat, link_target: Option<PathBuf>) -> Self {
+        FileMetadata {
+            modified: stat.mtime,
+            permissions: stat.perm,
+            uid: stat.uid,
+            gid: stat.gid,
+            link_target,
+        }
+    }
+}
+
 pub enum SelectedFileEntry {
     Local(LocalFileEntry),
     Remote(RemoteFileEntry),
     None,
 }
 
+/// One of the two halves of a file list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pane {
+    Local,
+    Remote,
+}
+
 #[derive(Clone)]
 enum SelectedFileEntryIndex {
     Local(usize),
@@ -46,6 +120,218 @@ enum SelectedFileEntryIndex {
     None,
 }
 
+/// What the entries of a file list are ordered by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortMode {
+    /// The file name, ignoring case and comparing numbers by their value.
+    Name,
+    Size,
+    Modified,
+    /// The file extension, and then the name.
+    Extension,
+}
+
+impl SortMode {
+    /// Return the mode after this one, to cycle through all of them.
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Name => SortMode::Size,
+            SortMode::Size => SortMode::Modified,
+            SortMode::Modified => SortMode::Extension,
+            SortMode::Extension => SortMode::Name,
+        }
+    }
+
+    /// Compare two entries in ascending order, falling back to their names.
+    fn compare<E: FileEntry>(self, a: &E, b: &E) -> Ordering {
+        let (a_name, b_name) = (
+            a.file_name_lossy().unwrap_or_default(),
+            b.file_name_lossy().unwrap_or_default(),
+        );
+        let by_name = || compare_names(&a_name, &b_name);
+        match self {
+            SortMode::Name => by_name(),
+            SortMode::Size => a.len().cmp(&b.len()).then_with(by_name),
+            SortMode::Modified => a.modified().cmp(&b.modified()).then_with(by_name),
+            SortMode::Extension => {
+                let extension = |entry: &E| {
+                    entry
+                        .path()
+                        .extension()
+                        .map(|extension| extension.to_string_lossy().to_lowercase())
+                };
+                extension(a).cmp(&extension(b)).then_with(by_name)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SortMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SortMode::Name => "name",
+            SortMode::Size => "size",
+            SortMode::Modified => "modified",
+            SortMode::Extension => "type",
+        })
+    }
+}
+
+/// How the entries of a file list are ordered. The parent directory always comes first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sort {
+    pub mode: SortMode,
+    pub descending: bool,
+    /// List the directories before the files, whatever the mode and direction.
+    pub directories_first: bool,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Sort {
+            mode: SortMode::Name,
+            descending: false,
+            directories_first: true,
+        }
+    }
+}
+
+impl Sort {
+    /// Sort `entries` in this order.
+    fn sort<E: FileEntry>(self, entries: &mut [E]) {
+        entries.sort_by(|a, b| {
+            b.is_parent()
+                .cmp(&a.is_parent())
+                .then_with(|| {
+                    if self.directories_first {
+                        b.is_dir().cmp(&a.is_dir())
+                    } else {
+                        Ordering::Equal
+                    }
+                })
+                .then_with(|| {
+                    let ordering = self.mode.compare(a, b);
+                    if self.descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                })
+        });
+    }
+}
+
+/// A short label for the titles of the panes, e.g., "name↑", followed by " mixed" if the
+/// directories are not listed first.
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            self.mode,
+            if self.descending { "↓" } else { "↑" }
+        )?;
+        if !self.directories_first {
+            f.write_str(" mixed")?;
+        }
+        Ok(())
+    }
+}
+
+/// Return the indices of the `entries` that are shown with `filter`, which are all of them if
+/// there is none. The parent directory is always shown.
+fn get_visible_indices<E: FileEntry>(entries: &[E], filter: Option<&Filter>) -> Vec<usize> {
+    (0..entries.len())
+        .filter(|&i| {
+            let entry = &entries[i];
+            entry.is_parent()
+                || filter.is_none_or(|filter| {
+                    get_match_name(entry).is_some_and(|name| filter.matches(&name))
+                })
+        })
+        .collect()
+}
+
+/// Return the name that filters and searches match `entry` with, unless it is the parent.
+fn get_match_name<E: FileEntry>(entry: &E) -> Option<String> {
+    if entry.is_parent() {
+        None
+    } else {
+        entry.file_name_lossy().map(Cow::into_owned)
+    }
+}
+
+/// Apply `f` to the position of the entry `i` among the shown entries `visible`, wrapping
+/// around at the ends, and return the index of the entry at the new position.
+///
+/// An entry that is not shown is at the position of the next one that is.
+fn apply_op_to_index<F>(visible: &[usize], i: usize, f: F) -> Option<usize>
+where
+    F: Fn(isize) -> isize,
+{
+    if visible.is_empty() {
+        return None;
+    }
+    let n = visible.len();
+    let position = visible.iter().position(|&j| j >= i).unwrap_or(n - 1);
+    Some(visible[f(position as isize).rem_euclid(n as isize) as usize])
+}
+
+/// Return the part of a pane title that shows its filter, if it has one.
+fn get_filter_label(filter: Option<&Filter>) -> String {
+    filter
+        .map(|filter| format!(" [filter: {}]", filter))
+        .unwrap_or_default()
+}
+
+/// Return the title of a pane that shows `path`, e.g., `Local: "/home" [name↑]`.
+///
+/// The labels of `sort` and `filter` are only added if they fit in `width` as a whole, so that
+/// the path is never cut off to make room for them.
+fn get_title(side: &str, path: &Path, sort: Sort, filter: Option<&Filter>, width: usize) -> String {
+    let mut title = format!("{}: {:?}", side, path);
+    for label in [format!(" [{}]", sort), get_filter_label(filter)] {
+        if title.chars().count() + label.chars().count() <= width {
+            title.push_str(&label);
+        }
+    }
+    title
+}
+
+/// Compare two file names in natural order, i.e., ignoring case and comparing runs of digits
+/// by their value so that `file2` comes before `file10`.
+///
+/// Names that only differ in case or leading zeros are compared as they are.
+fn compare_names(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let (x, y) = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(&x), Some(&y)) => (x, y),
+        };
+        let ordering = if x.is_ascii_digit() && y.is_ascii_digit() {
+            let take_number = |chars: &mut std::iter::Peekable<std::str::Chars<'_>>| {
+                let mut digits = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(c);
+                }
+                digits.trim_start_matches('0').to_string()
+            };
+            let (x, y) = (take_number(&mut a_chars), take_number(&mut b_chars));
+            x.len().cmp(&y.len()).then_with(|| x.cmp(&y))
+        } else {
+            a_chars.next();
+            b_chars.next();
+            x.to_lowercase().cmp(y.to_lowercase())
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct FileList {
     local_directory: PathBuf,
@@ -53,65 +339,702 @@ pub struct FileList {
     local_entries: Vec<LocalFileEntry>,
     remote_entries: Vec<RemoteFileEntry>,
     selected: SelectedFileEntryIndex,
+    sort: Sort,
+    /// The columns to show after the names, in the order they are dropped from last to first
+    /// when a pane is too narrow.
+    columns: Vec<Column>,
+    /// Only the entries that match are shown, until the directory changes.
+    local_filter: Option<Filter>,
+    remote_filter: Option<Filter>,
 }
 
-/// `CHUNK_SIZE` bytes of data is read from the source and then it is all written to the dest.
-const CHUNK_SIZE: usize = 8 * 1024;
+/// Options that control how files are transferred.
+#[derive(Clone, Copy)]
+pub struct TransferOptions {
+    /// If the destination is a shorter copy of the source, only transfer the remaining bytes.
+    pub resume: bool,
+    /// Continue from a partial file that an earlier attempt left behind, if there is one, but
+    /// otherwise start over. Retries use this so that only the file that failed is resumed.
+    pub resume_partial: bool,
+    /// The size of each read and write of a transfer, in `CHUNK_SIZE` SFTP requests.
+    pub requests: usize,
+    /// Compare the checksums of the source and the destination after each file is transferred.
+    pub verify: bool,
+    /// Copy the permissions and access/modification times of the source to the destination.
+    pub preserve: bool,
+}
 
-/// Reads the remote file `source`, creates/truncates the local file `dest`,
-/// and writes the data to `dest`.
+impl Default for TransferOptions {
+    fn default() -> Self {
+        TransferOptions {
+            resume: false,
+            resume_partial: false,
+            requests: DEFAULT_REQUESTS,
+            verify: false,
+            preserve: false,
+        }
+    }
+}
+
+/// The size of a single SFTP read or write request.
+const CHUNK_SIZE: usize = 32 * 1024;
+/// The default size of each read and write in SFTP requests, the number that OpenSSH's `sftp`
+/// keeps in flight.
+pub const DEFAULT_REQUESTS: usize = 64;
+/// How often a paused transfer checks whether it should continue.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// The error that libssh2 returns when the SFTP server replies with an error status.
+const LIBSSH2_ERROR_SFTP_PROTOCOL: i32 = -31;
+/// The number of bytes at the end of a partial destination that must match the source
+/// before a transfer is resumed.
+const RESUME_CHECK_SIZE: u64 = 64 * 1024;
+
+/// Reads the remote file `source` and writes the data to the local file `dest`.
+///
+/// The data is first written to the partial file `.<name>.rftp-part` which is renamed to `dest`
+/// only once the transfer has succeeded, so `dest` is never left truncated. If the transfer
+/// fails, the partial file is kept so that a transfer with `options.resume` can continue where
+/// it left off. It is only removed if the transfer is cancelled or its checksum does not match.
+///
+/// If `options.resume` is set and there is no partial file, but `dest` is a shorter copy of
+/// `source`, `dest` is incomplete and is renamed to the partial file to be continued there.
+/// If `options.resume_partial` is set, only an existing partial file is continued.
 pub fn download(
     source: RemoteFileEntry,
     dest: impl AsRef<Path>,
+    session: &ssh2::Session,
     sftp: &ssh2::Sftp,
     progress: &Progress,
+    rate_limiter: &RateLimiter,
+    options: TransferOptions,
 ) -> io::Result<()> {
-    assert!(source.is_file(), "Source must be a file!");
-    let mut source = sftp.open(&source.path())?;
-    let mut dest = File::create(dest)?;
-    let mut buffer = [0; CHUNK_SIZE];
+    let dest = dest.as_ref();
+    let partial = get_partial_path(dest);
+    let mut options = options;
+    options.resume |= options.resume_partial && partial.exists();
+    if options.resume && !partial.exists() && dest.is_file() {
+        let mut source_file = sftp.open(source.path())?;
+        let mut dest_file = File::open(dest)?;
+        let dest_len = dest_file.metadata()?.len();
+        let source_len = source.len().unwrap();
+        if is_shorter_copy(&mut source_file, source_len, &mut dest_file, dest_len)? {
+            rename(dest, &partial)?;
+        }
+    }
 
-    loop {
-        let bytes_read = source.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
+    let source_path = source.path().to_path_buf();
+    let result = download_partial(
+        source,
+        &partial,
+        session,
+        sftp,
+        progress,
+        rate_limiter,
+        options,
+    )
+    .and_then(|()| {
+        if options.preserve {
+            copy_remote_attributes(&source_path, &partial, sftp)
         } else {
-            dest.write_all(&buffer[..bytes_read])?;
-            progress.inc(bytes_read as u64);
+            Ok(())
         }
+    });
+    if result
+        .as_ref()
+        .is_err_and(|err| is_partial_useless(err, progress))
+    {
+        remove_file(&partial).ok();
     }
-
-    progress.finish();
+    result?;
+    rename(&partial, dest)?;
+    progress.inc_files();
     Ok(())
 }
 
-/// Reads the local file `source`, creates/truncates the remote file `dest`,
+/// Reads the remote file `source`, creates/truncates the local file `dest`,
 /// and writes the data to `dest`.
+///
+/// If `options.resume` is set and `dest` is a partial copy of `source`,
+/// only the missing bytes are appended to `dest`.
+/// If `options.verify` is set, the checksum of `dest` is compared with the checksum of `source`.
+fn download_partial(
+    source: RemoteFileEntry,
+    dest: &Path,
+    session: &ssh2::Session,
+    sftp: &ssh2::Sftp,
+    progress: &Progress,
+    rate_limiter: &RateLimiter,
+    options: TransferOptions,
+) -> io::Result<()> {
+    assert!(source.is_file(), "Source must be a file!");
+    let source_len = source.len().unwrap();
+    let source_path = source.path().to_path_buf();
+    let mut source = sftp.open(&source_path)?;
+    let mut checksum = if options.verify {
+        Some(Checksum::new())
+    } else {
+        None
+    };
+    let mut dest = if options.resume {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dest)?
+    } else {
+        File::create(dest)?
+    };
+
+    if options.resume {
+        let dest_len = dest.metadata()?.len();
+        let offset = resume_offset(&mut source, source_len, &mut dest, dest_len)?;
+        dest.set_len(offset)?;
+        if let Some(checksum) = &mut checksum {
+            dest.seek(SeekFrom::Start(0))?;
+            checksum.update_from_reader(&mut (&mut dest).take(offset))?;
+        }
+        source.seek(SeekFrom::Start(offset))?;
+        dest.seek(SeekFrom::Start(offset))?;
+        progress.skip(offset);
+    }
+
+    copy(
+        &mut source,
+        &mut dest,
+        progress,
+        rate_limiter,
+        options.requests,
+        checksum.as_mut(),
+    )?;
+    if let Some(checksum) = checksum {
+        verify_remote_checksum(&checksum, &source_path, session, sftp)?;
+    }
+    Ok(())
+}
+
+/// Reads the local file `source` and writes the data to the remote file `dest`.
+///
+/// The data is first written to the partial file `.<name>.rftp-part` which is renamed to `dest`
+/// only once the transfer has succeeded, so `dest` is never left truncated. If the transfer
+/// fails, the partial file is kept so that a transfer with `options.resume` can continue where
+/// it left off. It is only removed if the transfer is cancelled or its checksum does not match.
+///
+/// If `options.resume` is set and there is no partial file, but `dest` is a shorter copy of
+/// `source`, `dest` is incomplete and is renamed to the partial file to be continued there.
+/// If `options.resume_partial` is set, only an existing partial file is continued.
 pub fn upload(
     source: LocalFileEntry,
     dest: impl AsRef<Path>,
+    session: &ssh2::Session,
+    sftp: &ssh2::Sftp,
+    progress: &Progress,
+    rate_limiter: &RateLimiter,
+    options: TransferOptions,
+) -> io::Result<()> {
+    let dest = dest.as_ref();
+    let partial = get_partial_path(dest);
+    if options.resume
+        && sftp.lstat(&partial).is_err()
+        && sftp.stat(dest).is_ok_and(|stat| stat.is_file())
+    {
+        let mut source_file = File::open(source.path())?;
+        let mut dest_file = sftp.open(dest)?;
+        let dest_len = dest_file.stat()?.size.unwrap_or(0);
+        let source_len = source.len().unwrap();
+        if is_shorter_copy(&mut source_file, source_len, &mut dest_file, dest_len)? {
+            sftp.rename(dest, &partial, Some(ssh2::RenameFlags::empty()))?;
+        }
+    }
+    let mut options = options;
+    // The partial file is created if it does not exist, so the upload starts over then.
+    options.resume |= options.resume_partial;
+    let source_path = source.path().to_path_buf();
+    let result = upload_partial(
+        source,
+        &partial,
+        session,
+        sftp,
+        progress,
+        rate_limiter,
+        options,
+    )
+    .and_then(|()| {
+        if options.preserve {
+            copy_local_attributes(&source_path, &partial, sftp)
+        } else {
+            Ok(())
+        }
+    });
+    if result
+        .as_ref()
+        .is_err_and(|err| is_partial_useless(err, progress))
+    {
+        sftp.unlink(&partial).ok();
+    }
+    result?;
+    rename_remote(&partial, dest, sftp)?;
+    progress.inc_files();
+    Ok(())
+}
+
+/// Reads the local file `source`, creates/truncates the remote file `dest`,
+/// and writes the data to `dest`.
+///
+/// If `options.resume` is set and `dest` is a partial copy of `source`,
+/// only the missing bytes are appended to `dest`.
+/// If `options.verify` is set, the checksum of `dest` is compared with the checksum of `source`.
+fn upload_partial(
+    source: LocalFileEntry,
+    dest: &Path,
+    session: &ssh2::Session,
     sftp: &ssh2::Sftp,
     progress: &Progress,
+    rate_limiter: &RateLimiter,
+    options: TransferOptions,
 ) -> io::Result<()> {
     assert!(source.is_file(), "Source must be a file!");
+    let source_len = source.len().unwrap();
     let mut source = File::open(source.path())?;
-    let mut dest = sftp.create(dest.as_ref())?;
-    let mut buffer = [0; CHUNK_SIZE];
+    let mut checksum = if options.verify {
+        Some(Checksum::new())
+    } else {
+        None
+    };
+    let mut dest_file = if options.resume {
+        sftp.open_mode(
+            dest,
+            ssh2::OpenFlags::READ | ssh2::OpenFlags::WRITE | ssh2::OpenFlags::CREATE,
+            0o644,
+            ssh2::OpenType::File,
+        )?
+    } else {
+        sftp.create(dest)?
+    };
 
-    loop {
-        let bytes_read = source.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
+    if options.resume {
+        let dest_len = dest_file.stat()?.size.unwrap_or(0);
+        let offset = resume_offset(&mut source, source_len, &mut dest_file, dest_len)?;
+        if offset < dest_len {
+            dest_file.setstat(ssh2::FileStat {
+                size: Some(offset),
+                uid: None,
+                gid: None,
+                perm: None,
+                atime: None,
+                mtime: None,
+            })?;
+        }
+        if let Some(checksum) = &mut checksum {
+            source.seek(SeekFrom::Start(0))?;
+            checksum.update_from_reader(&mut (&mut source).take(offset))?;
+        }
+        source.seek(SeekFrom::Start(offset))?;
+        dest_file.seek(SeekFrom::Start(offset))?;
+        progress.skip(offset);
+    }
+
+    copy(
+        &mut source,
+        &mut dest_file,
+        progress,
+        rate_limiter,
+        options.requests,
+        checksum.as_mut(),
+    )?;
+    if let Some(checksum) = checksum {
+        verify_remote_checksum(&checksum, dest, session, sftp)?;
+    }
+    Ok(())
+}
+
+/// Returns the path of the partial file that is written while transferring to `dest`.
+fn get_partial_path(dest: &Path) -> PathBuf {
+    let file_name = dest.file_name().unwrap().to_string_lossy();
+    dest.with_file_name(format!(".{}.rftp-part", file_name))
+}
+
+/// Returns `true` if the partial file of a transfer that failed with `err` should be removed.
+///
+/// That is the case if the transfer was cancelled or if the checksums did not match, which
+/// `verify_remote_checksum` reports as `InvalidData`. After any other error, e.g., a dropped
+/// connection, the data in the partial file is fine and can be resumed.
+fn is_partial_useless(err: &io::Error, progress: &Progress) -> bool {
+    progress.is_cancelled() || err.kind() == io::ErrorKind::InvalidData
+}
+
+/// Renames the remote file `source` to `dest`, replacing `dest` if it exists.
+///
+/// Many SFTP servers refuse to rename over an existing file. If the server refused and `dest`
+/// is a file, it is removed and the rename is tried again. If that fails too, the error says
+/// that the data is left in `source`.
+fn rename_remote(source: &Path, dest: &Path, sftp: &ssh2::Sftp) -> io::Result<()> {
+    let flags =
+        ssh2::RenameFlags::OVERWRITE | ssh2::RenameFlags::ATOMIC | ssh2::RenameFlags::NATIVE;
+    let err = match sftp.rename(source, dest, Some(flags)) {
+        Ok(()) => return Ok(()),
+        Err(err) => err,
+    };
+    // Any other error, e.g., a dropped connection, would not be fixed by removing `dest`.
+    let is_dest_in_the_way = err.code() == LIBSSH2_ERROR_SFTP_PROTOCOL
+        && sftp.stat(dest).is_ok_and(|stat| stat.is_file());
+    if !is_dest_in_the_way {
+        return Err(err.into());
+    }
+    sftp.unlink(dest)?;
+    sftp.rename(source, dest, Some(flags)).map_err(|err| {
+        io::Error::other(format!(
+            "{} while replacing {:?}, the upload is stored as {:?}",
+            err, dest, source
+        ))
+    })
+}
+
+/// Copies everything that is left in `source` to `dest`, reports the progress,
+/// and adds the copied data to `checksum`.
+///
+/// Every read and write is `requests * CHUNK_SIZE` bytes, which gives libssh2 room to have
+/// more than one SFTP request in flight at a time. Reading happens on a separate thread so that
+/// the next buffer is read while the last one is being written. `bench_copy` measures how much
+/// this helps over a link with latency.
+///
+/// While `rate_limiter` has a limit, data is read and written one `CHUNK_SIZE` at a time so
+/// that the transfer does not burst above the limit.
+fn copy(
+    source: &mut (impl Read + Send),
+    dest: &mut impl Write,
+    progress: &Progress,
+    rate_limiter: &RateLimiter,
+    requests: usize,
+    mut checksum: Option<&mut Checksum>,
+) -> io::Result<()> {
+    let buffer_size = requests.max(1) * CHUNK_SIZE;
+    let (sender, receiver) = bounded(1);
+
+    thread::scope(|scope| {
+        scope.spawn(move || loop {
+            let buffer_size = if rate_limiter.is_limited() {
+                CHUNK_SIZE
+            } else {
+                buffer_size
+            };
+            let mut buffer = vec![0; buffer_size];
+            let result = source.read(&mut buffer).map(|bytes_read| {
+                buffer.truncate(bytes_read);
+                buffer
+            });
+            let is_done = !matches!(result, Ok(ref buffer) if !buffer.is_empty());
+            // The receiver is dropped if writing fails, in which case we stop reading.
+            if sender.send(result).is_err() || is_done {
+                break;
+            }
+        });
+
+        for buffer in receiver {
+            check_progress_state(progress)?;
+            let buffer = buffer?;
+            if buffer.is_empty() {
+                break;
+            }
+            if let Some(checksum) = checksum.as_mut() {
+                checksum.update(&buffer);
+            }
+            if rate_limiter.is_limited() {
+                for chunk in buffer.chunks(CHUNK_SIZE) {
+                    wait_for_rate_limit(progress, rate_limiter, chunk.len() as u64)?;
+                    dest.write_all(chunk)?;
+                    progress.inc(chunk.len() as u64);
+                }
+            } else {
+                dest.write_all(&buffer)?;
+                progress.inc(buffer.len() as u64);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Blocks until `bytes` can be sent within the limit of `rate_limiter`.
+///
+/// Returns an error if `progress` is cancelled while waiting.
+fn wait_for_rate_limit(
+    progress: &Progress,
+    rate_limiter: &RateLimiter,
+    bytes: u64,
+) -> io::Result<()> {
+    let mut wait = rate_limiter.reserve(bytes);
+    while wait > Duration::from_secs(0) {
+        let interval = wait.min(PAUSE_POLL_INTERVAL);
+        thread::sleep(interval);
+        wait -= interval;
+        check_progress_state(progress)?;
+    }
+    Ok(())
+}
+
+/// Blocks while `progress` is paused and returns an error if it has been cancelled.
+fn check_progress_state(progress: &Progress) -> io::Result<()> {
+    while progress.is_paused() {
+        thread::sleep(PAUSE_POLL_INTERVAL);
+    }
+    if progress.is_cancelled() {
+        Err(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "the transfer was cancelled",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the offset at which a transfer from `source` to a partial `dest` should continue.
+///
+/// The transfer can continue at the end of `dest` if it is not longer than `source` and the
+/// last `RESUME_CHECK_SIZE` bytes of `dest` match `source`. Otherwise it must start over at zero.
+fn resume_offset(
+    source: &mut (impl Read + Seek),
+    source_len: u64,
+    dest: &mut (impl Read + Seek),
+    dest_len: u64,
+) -> io::Result<u64> {
+    if dest_len == 0 || dest_len > source_len {
+        return Ok(0);
+    }
+
+    let check_len = dest_len.min(RESUME_CHECK_SIZE);
+    let start = dest_len - check_len;
+    if read_range(source, start, check_len)? == read_range(dest, start, check_len)? {
+        Ok(dest_len)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Returns `true` if `dest` is a non-empty copy of the start of `source` that is shorter than
+/// `source`, going by the same check as `resume_offset`.
+fn is_shorter_copy(
+    source: &mut (impl Read + Seek),
+    source_len: u64,
+    dest: &mut (impl Read + Seek),
+    dest_len: u64,
+) -> io::Result<bool> {
+    let offset = resume_offset(source, source_len, dest, dest_len)?;
+    Ok(offset > 0 && offset < source_len)
+}
+
+/// Reads `len` bytes of `file` starting at `start`.
+fn read_range(file: &mut (impl Read + Seek), start: u64, len: u64) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0; len as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Recursively downloads the remote directory `source` into the local directory `dest`,
+/// creating `dest` and any missing subdirectories along the way.
+pub fn download_directory(
+    source: RemoteFileEntry,
+    dest: impl AsRef<Path>,
+    session: &ssh2::Session,
+    sftp: &ssh2::Sftp,
+    progress: &Progress,
+    rate_limiter: &RateLimiter,
+    options: TransferOptions,
+) -> io::Result<()> {
+    assert!(source.is_dir(), "Source must be a directory!");
+    let entries = walk_remote_directory(source.path(), sftp)?;
+    set_progress_total(progress, &entries);
+
+    let get_dest = |entry: &RemoteFileEntry| {
+        dest.as_ref()
+            .join(entry.path().strip_prefix(source.path()).unwrap())
+    };
+
+    create_dir_all(&dest)?;
+    for entry in entries.iter() {
+        check_progress_state(progress)?;
+        if entry.is_dir() {
+            create_dir_all(get_dest(entry))?;
+        } else {
+            download(
+                entry.clone(),
+                get_dest(entry),
+                session,
+                sftp,
+                progress,
+                rate_limiter,
+                options,
+            )?;
+        }
+    }
+
+    if options.preserve {
+        // Adding files to a directory changes its times, so directories are updated last,
+        // deepest first.
+        for entry in entries.iter().rev().filter(|entry| entry.is_dir()) {
+            copy_remote_attributes(entry.path(), &get_dest(entry), sftp)?;
+        }
+        copy_remote_attributes(source.path(), dest.as_ref(), sftp)?;
+    }
+    Ok(())
+}
+
+/// Recursively uploads the local directory `source` into the remote directory `dest`,
+/// creating `dest` and any missing subdirectories along the way.
+pub fn upload_directory(
+    source: LocalFileEntry,
+    dest: impl AsRef<Path>,
+    session: &ssh2::Session,
+    sftp: &ssh2::Sftp,
+    progress: &Progress,
+    rate_limiter: &RateLimiter,
+    options: TransferOptions,
+) -> io::Result<()> {
+    assert!(source.is_dir(), "Source must be a directory!");
+    let entries = walk_local_directory(source.path())?;
+    set_progress_total(progress, &entries);
+
+    let get_dest = |entry: &LocalFileEntry| {
+        dest.as_ref()
+            .join(entry.path().strip_prefix(source.path()).unwrap())
+    };
+
+    create_remote_dir(dest.as_ref(), sftp)?;
+    for entry in entries.iter() {
+        check_progress_state(progress)?;
+        if entry.is_dir() {
+            create_remote_dir(&get_dest(entry), sftp)?;
         } else {
-            dest.write_all(&buffer[..bytes_read])?;
-            progress.inc(bytes_read as u64);
+            upload(
+                entry.clone(),
+                get_dest(entry),
+                session,
+                sftp,
+                progress,
+                rate_limiter,
+                options,
+            )?;
+        }
+    }
+
+    if options.preserve {
+        // Adding files to a directory changes its times, so directories are updated last,
+        // deepest first.
+        for entry in entries.iter().rev().filter(|entry| entry.is_dir()) {
+            copy_local_attributes(entry.path(), &get_dest(entry), sftp)?;
+        }
+        copy_local_attributes(source.path(), dest.as_ref(), sftp)?;
+    }
+    Ok(())
+}
+
+/// Returns every file and directory below the local directory `dir`.
+///
+/// Directories are listed before their contents. Symbolic links to directories are not followed.
+pub fn walk_local_directory(dir: &Path) -> io::Result<Vec<LocalFileEntry>> {
+    let mut entries = vec![];
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let link_metadata = entry.metadata()?;
+        if link_metadata.is_dir() {
+            let metadata = FileMetadata::from_local(&link_metadata, None);
+            entries.push(LocalFileEntry::Directory(path.clone(), metadata));
+            entries.extend(walk_local_directory(&path)?);
+        } else if path.is_file() {
+            let len = metadata(&path)?.len();
+            let link_target = read_link(&path).ok();
+            let metadata = FileMetadata::from_local(&link_metadata, link_target);
+            entries.push(LocalFileEntry::File(path, len, metadata));
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns every file and directory below the remote directory `dir`.
+///
+/// Directories are listed before their contents. Symbolic links are skipped.
+pub fn walk_remote_directory(dir: &Path, sftp: &ssh2::Sftp) -> io::Result<Vec<RemoteFileEntry>> {
+    let mut entries = vec![];
+    for (path, stat) in sftp.readdir(dir)? {
+        if stat.is_dir() {
+            let metadata = FileMetadata::from_remote(&stat, None);
+            entries.push(RemoteFileEntry::Directory(path.clone(), metadata));
+            entries.extend(walk_remote_directory(&path, sftp)?);
+        } else if stat.is_file() {
+            let metadata = FileMetadata::from_remote(&stat, None);
+            entries.push(RemoteFileEntry::File(
+                path,
+                stat.size.unwrap_or(0),
+                metadata,
+            ));
         }
     }
+    Ok(entries)
+}
 
-    progress.finish();
+/// Copies the permissions and access/modification times of the remote `source` to the local `dest`.
+fn copy_remote_attributes(source: &Path, dest: &Path, sftp: &ssh2::Sftp) -> io::Result<()> {
+    let stat = sftp.stat(source)?;
+    #[cfg(unix)]
+    {
+        use std::fs::{set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(perm) = stat.perm {
+            set_permissions(dest, Permissions::from_mode(perm & 0o7777))?;
+        }
+    }
+    if let (Some(atime), Some(mtime)) = (stat.atime, stat.mtime) {
+        set_file_times(
+            dest,
+            FileTime::from_unix_time(atime as i64, 0),
+            FileTime::from_unix_time(mtime as i64, 0),
+        )?;
+    }
     Ok(())
 }
 
+/// Copies the permissions and access/modification times of the local `source` to the remote `dest`.
+fn copy_local_attributes(source: &Path, dest: &Path, sftp: &ssh2::Sftp) -> io::Result<()> {
+    let metadata = metadata(source)?;
+    #[cfg(unix)]
+    let perm = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o7777)
+    };
+    #[cfg(not(unix))]
+    let perm = None;
+    let atime = FileTime::from_last_access_time(&metadata).unix_seconds();
+    let mtime = FileTime::from_last_modification_time(&metadata).unix_seconds();
+    sftp.setstat(
+        dest,
+        ssh2::FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm,
+            atime: Some(atime.max(0) as u64),
+            mtime: Some(mtime.max(0) as u64),
+        },
+    )?;
+    Ok(())
+}
+
+/// Creates the remote directory `path` if it does not already exist.
+fn create_remote_dir(path: &Path, sftp: &ssh2::Sftp) -> io::Result<()> {
+    match sftp.stat(path) {
+        Ok(stat) if stat.is_dir() => Ok(()),
+        _ => Ok(sftp.mkdir(path, 0o755)?),
+    }
+}
+
+/// Sets the total number of bytes and files of `progress` to the sum of the files in `entries`.
+fn set_progress_total<E: FileEntry>(progress: &Progress, entries: &[E]) {
+    let files = entries.iter().filter(|entry| entry.is_file());
+    let total_bytes = files.clone().filter_map(|entry| entry.len()).sum();
+    progress.set_total(total_bytes, files.count() as u64);
+}
+
 pub trait FileEntry {
     /// Return the full path of this entry.
     fn path(&self) -> &Path;
@@ -123,9 +1046,16 @@ pub trait FileEntry {
     fn is_parent(&self) -> bool;
     /// Return the size of this entry.
     fn len(&self) -> Option<u64>;
+    /// Return the metadata of this entry, unless it is a parent directory.
+    fn metadata(&self) -> Option<&FileMetadata>;
+
+    /// Return the modification time of this entry in seconds since the Unix epoch.
+    fn modified(&self) -> Option<u64> {
+        self.metadata().and_then(|metadata| metadata.modified)
+    }
 
     /// Return the file name of this entry.
-    fn file_name_lossy(&self) -> Option<Cow<str>> {
+    fn file_name_lossy(&self) -> Option<Cow<'_, str>> {
         self.path()
             .file_name()
             .map(|file_name| file_name.to_string_lossy())
@@ -138,35 +1068,24 @@ pub trait FileEntry {
         } else {
             // A file is hidden if its name begins with a '.'
             let filename = self.path().file_name().unwrap();
-            filename.to_str().unwrap().chars().next() == Some('.')
+            filename.to_str().unwrap().starts_with('.')
         }
     }
 
-    /// Returns the text of this entry for displaying to the user.
-    fn to_text(&self, width: usize) -> Text {
+    /// Returns the text of this entry with `columns` for displaying to the user.
+    fn to_text(&self, width: usize, columns: &[Column]) -> Text<'_> {
         if self.is_parent() {
             // TODO: We can either use the emoji "⬅" or ".." for the parent directory.
             // Text::Styled(Cow::Borrowed(".."), Style::default().fg(Color::Red))
             Text::raw("⬅")
         } else if self.is_file() {
-            let file_len_string = self
-                .len()
-                .map(|len| bytes_to_string(len))
-                .unwrap_or(String::from(""));
-            let width = width - (file_len_string.len() + 1);
             Text::styled(
-                format!(
-                    "{name:min_width$.max_width$} {len}",
-                    name = self.file_name_lossy().unwrap(),
-                    min_width = width,
-                    max_width = width,
-                    len = file_len_string
-                ),
+                format_entry(self, columns, width),
                 Style::default().fg(FILELIST_FILE_COLOR),
             )
         } else if self.is_dir() {
             Text::styled(
-                format!("{}/", self.file_name_lossy().unwrap()),
+                format_entry(self, columns, width),
                 Style::default().fg(FILELIST_DIRECTORY_COLOR),
             )
         } else {
@@ -178,40 +1097,48 @@ pub trait FileEntry {
 impl FileEntry for LocalFileEntry {
     fn path(&self) -> &Path {
         match self {
-            LocalFileEntry::File(path, _) => path,
-            LocalFileEntry::Directory(path) => path,
+            LocalFileEntry::File(path, _, _) => path,
+            LocalFileEntry::Directory(path, _) => path,
             LocalFileEntry::Parent(path) => path,
         }
     }
 
     fn is_dir(&self) -> bool {
         match self {
-            LocalFileEntry::File(_, _) => false,
-            LocalFileEntry::Directory(_) => true,
+            LocalFileEntry::File(_, _, _) => false,
+            LocalFileEntry::Directory(_, _) => true,
             LocalFileEntry::Parent(_) => true,
         }
     }
 
     fn is_file(&self) -> bool {
         match self {
-            LocalFileEntry::File(_, _) => true,
-            LocalFileEntry::Directory(_) => false,
+            LocalFileEntry::File(_, _, _) => true,
+            LocalFileEntry::Directory(_, _) => false,
             LocalFileEntry::Parent(_) => false,
         }
     }
 
     fn is_parent(&self) -> bool {
         match self {
-            LocalFileEntry::File(_, _) => false,
-            LocalFileEntry::Directory(_) => false,
+            LocalFileEntry::File(_, _, _) => false,
+            LocalFileEntry::Directory(_, _) => false,
             LocalFileEntry::Parent(_) => true,
         }
     }
 
     fn len(&self) -> Option<u64> {
         match self {
-            LocalFileEntry::File(_, len) => Some(*len),
-            LocalFileEntry::Directory(_) => None,
+            LocalFileEntry::File(_, len, _) => Some(*len),
+            LocalFileEntry::Directory(_, _) => None,
+            LocalFileEntry::Parent(_) => None,
+        }
+    }
+
+    fn metadata(&self) -> Option<&FileMetadata> {
+        match self {
+            LocalFileEntry::File(_, _, metadata) => Some(metadata),
+            LocalFileEntry::Directory(_, metadata) => Some(metadata),
             LocalFileEntry::Parent(_) => None,
         }
     }
@@ -220,53 +1147,67 @@ impl FileEntry for LocalFileEntry {
 impl FileEntry for RemoteFileEntry {
     fn path(&self) -> &Path {
         match self {
-            RemoteFileEntry::File(path, _) => path,
-            RemoteFileEntry::Directory(path) => path,
+            RemoteFileEntry::File(path, _, _) => path,
+            RemoteFileEntry::Directory(path, _) => path,
             RemoteFileEntry::Parent(path) => path,
         }
     }
 
     fn is_dir(&self) -> bool {
         match self {
-            RemoteFileEntry::File(_, _) => false,
-            RemoteFileEntry::Directory(_) => true,
+            RemoteFileEntry::File(_, _, _) => false,
+            RemoteFileEntry::Directory(_, _) => true,
             RemoteFileEntry::Parent(_) => true,
         }
     }
 
     fn is_file(&self) -> bool {
         match self {
-            RemoteFileEntry::File(_, _) => true,
-            RemoteFileEntry::Directory(_) => false,
+            RemoteFileEntry::File(_, _, _) => true,
+            RemoteFileEntry::Directory(_, _) => false,
             RemoteFileEntry::Parent(_) => false,
         }
     }
 
     fn is_parent(&self) -> bool {
         match self {
-            RemoteFileEntry::File(_, _) => false,
-            RemoteFileEntry::Directory(_) => false,
+            RemoteFileEntry::File(_, _, _) => false,
+            RemoteFileEntry::Directory(_, _) => false,
             RemoteFileEntry::Parent(_) => true,
         }
     }
 
     fn len(&self) -> Option<u64> {
         match self {
-            RemoteFileEntry::File(_, len) => Some(*len),
-            RemoteFileEntry::Directory(_) => None,
+            RemoteFileEntry::File(_, len, _) => Some(*len),
+            RemoteFileEntry::Directory(_, _) => None,
+            RemoteFileEntry::Parent(_) => None,
+        }
+    }
+
+    fn metadata(&self) -> Option<&FileMetadata> {
+        match self {
+            RemoteFileEntry::File(_, _, metadata) => Some(metadata),
+            RemoteFileEntry::Directory(_, metadata) => Some(metadata),
             RemoteFileEntry::Parent(_) => None,
         }
     }
 }
 
 impl FileList {
+    /// Create a file list of the current local directory and of `remote_path`, or the remote
+    /// home directory if it is not given.
     pub fn new(
         session: &ssh2::Session,
         sftp: &ssh2::Sftp,
+        remote_path: Option<&Path>,
         keep_hidden_files: bool,
     ) -> Result<Self, Box<dyn Error>> {
         let local_path = env::current_dir()?;
-        let remote_path = get_remote_home_dir(session).unwrap_or(PathBuf::from("./"));
+        let remote_path = match remote_path {
+            Some(remote_path) => remote_path.to_path_buf(),
+            None => get_remote_home_dir(session, sftp).unwrap_or(PathBuf::from("./")),
+        };
 
         let mut list = FileList {
             local_directory: PathBuf::new(),
@@ -274,9 +1215,14 @@ impl FileList {
             local_entries: vec![],
             remote_entries: vec![],
             selected: SelectedFileEntryIndex::None,
+            sort: Sort::default(),
+            columns: vec![Column::Size],
+            local_filter: None,
+            remote_filter: None,
         };
         list.set_local_working_path(local_path, keep_hidden_files)?;
-        list.set_remote_working_path(remote_path, sftp, keep_hidden_files)?;
+        list.set_remote_working_path(&remote_path, sftp, keep_hidden_files)
+            .map_err(|err| format!("unable to open remote directory {:?}: {}", remote_path, err))?;
         Ok(list)
     }
 
@@ -289,17 +1235,32 @@ impl FileList {
         }
         for entry in read_dir(&self.local_directory)? {
             let path = entry?.path();
-            if path.is_file() {
-                let len = metadata(&path)?.len();
-                self.local_entries.push(LocalFileEntry::File(path, len));
+            let link_metadata = symlink_metadata(&path)?;
+            let link_target = if link_metadata.file_type().is_symlink() {
+                read_link(&path).ok()
             } else {
-                self.local_entries.push(LocalFileEntry::Directory(path));
+                None
+            };
+            let file_metadata = FileMetadata::from_local(&link_metadata, link_target);
+            // Symbolic links are followed to find out whether they point to a file.
+            match metadata(&path) {
+                Ok(metadata) if metadata.is_file() => {
+                    self.local_entries.push(LocalFileEntry::File(
+                        path,
+                        metadata.len(),
+                        file_metadata,
+                    ));
+                }
+                _ => {
+                    self.local_entries
+                        .push(LocalFileEntry::Directory(path, file_metadata));
+                }
             }
         }
         if !keep_hidden_files {
             self.local_entries.retain(|entry| !entry.is_hidden());
         }
-        self.local_entries.sort_unstable();
+        self.sort.sort(&mut self.local_entries);
         Ok(())
     }
 
@@ -314,53 +1275,251 @@ impl FileList {
             self.remote_entries
                 .push(RemoteFileEntry::Parent(parent.to_path_buf()));
         }
-        self.remote_entries
-            .extend(
-                sftp.readdir(&self.remote_directory)?
-                    .iter()
-                    .map(|(path, stat)| {
-                        if stat.is_file() {
-                            RemoteFileEntry::File(path.to_path_buf(), stat.size.unwrap())
-                        } else {
-                            RemoteFileEntry::Directory(path.to_path_buf())
-                        }
-                    }),
-            );
+        for (path, link_stat) in sftp.readdir(&self.remote_directory)? {
+            // Symbolic links are followed to find out whether they point to a file.
+            let (stat, link_target) = if link_stat.file_type().is_symlink() {
+                let stat = sftp.stat(&path).unwrap_or_else(|_| link_stat.clone());
+                (stat, sftp.readlink(&path).ok())
+            } else {
+                (link_stat.clone(), None)
+            };
+            let metadata = FileMetadata::from_remote(&link_stat, link_target);
+            if stat.is_file() {
+                self.remote_entries.push(RemoteFileEntry::File(
+                    path,
+                    stat.size.unwrap_or(0),
+                    metadata,
+                ));
+            } else {
+                self.remote_entries
+                    .push(RemoteFileEntry::Directory(path, metadata));
+            }
+        }
         if !keep_hidden_files {
             self.remote_entries.retain(|entry| !entry.is_hidden());
         }
-        self.remote_entries.sort_unstable();
+        self.sort.sort(&mut self.remote_entries);
         Ok(())
     }
 
     /// Set the current local directory and then fetch its local files.
+    ///
+    /// If the directory cannot be read, the current one is kept.
     pub fn set_local_working_path(
         &mut self,
         path: impl AsRef<Path>,
         keep_hidden_files: bool,
     ) -> io::Result<()> {
-        self.local_directory = canonicalize(path)?;
-        self.fetch_local_files(keep_hidden_files)?;
+        let path = canonicalize(path)?;
+        let previous_directory = replace(&mut self.local_directory, path);
+        let previous_entries = take(&mut self.local_entries);
+        if let Err(err) = self.fetch_local_files(keep_hidden_files) {
+            self.local_directory = previous_directory;
+            self.local_entries = previous_entries;
+            return Err(err);
+        }
+        if self.local_directory != previous_directory {
+            self.local_filter = None;
+        }
         // Make sure we have a valid entry selected.
         self.apply_op_to_selected(|i| i);
         Ok(())
     }
 
     /// Set the current remote directory and then fetch its remote files.
+    ///
+    /// The server resolves `path` to an absolute path without any `..` or symbolic links. If
+    /// the directory cannot be read, the current one is kept.
     pub fn set_remote_working_path(
         &mut self,
         path: impl AsRef<Path>,
         sftp: &ssh2::Sftp,
         keep_hidden_files: bool,
     ) -> io::Result<()> {
-        // TODO: canonicalize
-        self.remote_directory = path.as_ref().to_path_buf();
-        self.fetch_remote_files(sftp, keep_hidden_files)?;
+        let path = sftp.realpath(path.as_ref())?;
+        let previous_directory = replace(&mut self.remote_directory, path);
+        let previous_entries = take(&mut self.remote_entries);
+        if let Err(err) = self.fetch_remote_files(sftp, keep_hidden_files) {
+            self.remote_directory = previous_directory;
+            self.remote_entries = previous_entries;
+            return Err(err);
+        }
+        if self.remote_directory != previous_directory {
+            self.remote_filter = None;
+        }
         // Make sure we have a valid entry selected.
         self.apply_op_to_selected(|i| i);
         Ok(())
     }
 
+    /// Fetch the entries of both directories again, e.g., after hidden files are shown or
+    /// hidden, and keep the selected entry selected if it is still listed.
+    ///
+    /// The remote entries are only fetched if `sftp` is given.
+    pub fn refresh(
+        &mut self,
+        sftp: Option<&ssh2::Sftp>,
+        keep_hidden_files: bool,
+    ) -> io::Result<()> {
+        let selected_path = self.get_selected_path();
+        self.fetch_local_files(keep_hidden_files)?;
+        if let Some(sftp) = sftp {
+            self.fetch_remote_files(sftp, keep_hidden_files)?;
+        }
+        self.reselect(selected_path);
+        Ok(())
+    }
+
+    /// Return the order of the entries.
+    pub fn get_sort(&self) -> Sort {
+        self.sort
+    }
+
+    /// Sort the entries of both directories in a new order and keep the selected entry selected.
+    pub fn set_sort(&mut self, sort: Sort) {
+        let selected_path = self.get_selected_path();
+        self.sort = sort;
+        sort.sort(&mut self.local_entries);
+        sort.sort(&mut self.remote_entries);
+        self.reselect(selected_path);
+    }
+
+    /// Set the columns to show after the names of the entries.
+    pub fn set_columns(&mut self, columns: Vec<Column>) {
+        self.columns = columns;
+    }
+
+    /// Return the pane of the selected entry.
+    pub fn get_selected_pane(&self) -> Option<Pane> {
+        match self.selected {
+            SelectedFileEntryIndex::Local(_) => Some(Pane::Local),
+            SelectedFileEntryIndex::Remote(_) => Some(Pane::Remote),
+            SelectedFileEntryIndex::None => None,
+        }
+    }
+
+    /// Return the filter of `pane`.
+    pub fn get_filter(&self, pane: Pane) -> Option<&Filter> {
+        match pane {
+            Pane::Local => self.local_filter.as_ref(),
+            Pane::Remote => self.remote_filter.as_ref(),
+        }
+    }
+
+    /// Hide the entries of `pane` that do not match `filter`, or show all of them again if
+    /// `filter` is `None`.
+    ///
+    /// The parent directory is always shown.
+    pub fn set_filter(&mut self, pane: Pane, filter: Option<Filter>) {
+        match pane {
+            Pane::Local => self.local_filter = filter,
+            Pane::Remote => self.remote_filter = filter,
+        }
+        // If the filter hid every entry, select one in the same pane again.
+        if let SelectedFileEntryIndex::None = self.selected {
+            self.selected = match pane {
+                Pane::Local => SelectedFileEntryIndex::Local(0),
+                Pane::Remote => SelectedFileEntryIndex::Remote(0),
+            };
+        }
+        // Make sure we have a valid entry selected.
+        self.apply_op_to_selected(|i| i);
+    }
+
+    /// Select the next shown entry in the pane with the selected entry whose name matches
+    /// `filter`, or the previous one if `reverse` is set, wrapping around at the end.
+    ///
+    /// The selected entry itself is only checked last, unless `include_selected` is set.
+    /// Return `false` if no entry matches.
+    pub fn select_match(&mut self, filter: &Filter, include_selected: bool, reverse: bool) -> bool {
+        let (i, visible, names): (_, _, Vec<_>) = match self.selected {
+            SelectedFileEntryIndex::Local(i) => (
+                i,
+                self.get_visible_local_indices(),
+                self.local_entries.iter().map(get_match_name).collect(),
+            ),
+            SelectedFileEntryIndex::Remote(i) => (
+                i,
+                self.get_visible_remote_indices(),
+                self.remote_entries.iter().map(get_match_name).collect(),
+            ),
+            SelectedFileEntryIndex::None => return false,
+        };
+        let n = visible.len();
+        let start = visible.iter().position(|&j| j == i).unwrap_or(0);
+        let offsets: Box<dyn Iterator<Item = usize>> = if include_selected {
+            Box::new(0..n)
+        } else {
+            Box::new((1..=n).map(|offset| offset % n))
+        };
+        let found = offsets
+            .map(|offset| {
+                if reverse {
+                    visible[(start + n - offset) % n]
+                } else {
+                    visible[(start + offset) % n]
+                }
+            })
+            .find(|&j| names[j].as_deref().is_some_and(|name| filter.matches(name)));
+        match (found, &mut self.selected) {
+            (Some(j), SelectedFileEntryIndex::Local(i))
+            | (Some(j), SelectedFileEntryIndex::Remote(i)) => {
+                *i = j;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Return the indices of the local entries that are shown.
+    fn get_visible_local_indices(&self) -> Vec<usize> {
+        get_visible_indices(&self.local_entries, self.local_filter.as_ref())
+    }
+
+    /// Return the indices of the remote entries that are shown.
+    fn get_visible_remote_indices(&self) -> Vec<usize> {
+        get_visible_indices(&self.remote_entries, self.remote_filter.as_ref())
+    }
+
+    /// Return the path of the currently selected file entry.
+    pub fn get_selected_path(&self) -> Option<PathBuf> {
+        match self.get_selected_entry() {
+            SelectedFileEntry::Local(entry) => Some(entry.path().to_path_buf()),
+            SelectedFileEntry::Remote(entry) => Some(entry.path().to_path_buf()),
+            SelectedFileEntry::None => None,
+        }
+    }
+
+    /// Select the entry at `selected_path` again after the entries have changed, or else the
+    /// entry at the same position.
+    pub fn reselect(&mut self, selected_path: Option<PathBuf>) {
+        let find = |paths: Vec<&Path>, i: usize| {
+            paths
+                .iter()
+                .position(|&path| Some(path) == selected_path.as_deref())
+                .unwrap_or_else(|| i.min(paths.len().saturating_sub(1)))
+        };
+        self.selected = match self.selected {
+            SelectedFileEntryIndex::Local(i) => SelectedFileEntryIndex::Local(find(
+                self.local_entries
+                    .iter()
+                    .map(|entry| entry.path())
+                    .collect(),
+                i,
+            )),
+            SelectedFileEntryIndex::Remote(i) => SelectedFileEntryIndex::Remote(find(
+                self.remote_entries
+                    .iter()
+                    .map(|entry| entry.path())
+                    .collect(),
+                i,
+            )),
+            SelectedFileEntryIndex::None => SelectedFileEntryIndex::None,
+        };
+        // Make sure we have a valid entry selected.
+        self.apply_op_to_selected(|i| i);
+    }
+
     /// Return the current local directory.
     pub fn get_local_working_path(&self) -> &Path {
         &self.local_directory
@@ -387,9 +1546,14 @@ impl FileList {
     }
 
     /// Return the index of the currently selected file entry if a local file entry is selected.
+    ///
+    /// The index is among the entries that are shown.
     pub fn get_local_selected_index(&self) -> ListState {
         let index = match self.selected {
-            SelectedFileEntryIndex::Local(i) => Some(i),
+            SelectedFileEntryIndex::Local(i) => self
+                .get_visible_local_indices()
+                .iter()
+                .position(|&j| j == i),
             _ => None,
         };
         let mut state = ListState::default();
@@ -398,9 +1562,14 @@ impl FileList {
     }
 
     /// Return the index of the currently selected file entry if a remote file entry is selected.
+    ///
+    /// The index is among the entries that are shown.
     pub fn get_remote_selected_index(&self) -> ListState {
         let index = match self.selected {
-            SelectedFileEntryIndex::Remote(i) => Some(i),
+            SelectedFileEntryIndex::Remote(i) => self
+                .get_visible_remote_indices()
+                .iter()
+                .position(|&j| j == i),
             _ => None,
         };
         let mut state = ListState::default();
@@ -408,33 +1577,30 @@ impl FileList {
         state
     }
 
-    /// Apply `f` to the index of the currently selected file entry.
+    /// Apply `f` to the position of the currently selected file entry among the entries that
+    /// are shown.
     fn apply_op_to_selected<F>(&mut self, f: F)
     where
         F: Fn(isize) -> isize,
     {
         self.selected = match self.selected {
             SelectedFileEntryIndex::Local(i) => {
-                if self.local_entries.is_empty() {
-                    SelectedFileEntryIndex::None
-                } else {
-                    let n = self.local_entries.len();
-                    SelectedFileEntryIndex::Local(f(i as isize).rem_euclid(n as isize) as usize)
+                match apply_op_to_index(&self.get_visible_local_indices(), i, f) {
+                    Some(i) => SelectedFileEntryIndex::Local(i),
+                    None => SelectedFileEntryIndex::None,
                 }
             }
             SelectedFileEntryIndex::Remote(i) => {
-                if self.remote_entries.is_empty() {
-                    SelectedFileEntryIndex::None
-                } else {
-                    let n = self.remote_entries.len();
-                    SelectedFileEntryIndex::Remote(f(i as isize).rem_euclid(n as isize) as usize)
+                match apply_op_to_index(&self.get_visible_remote_indices(), i, f) {
+                    Some(i) => SelectedFileEntryIndex::Remote(i),
+                    None => SelectedFileEntryIndex::None,
                 }
             }
             SelectedFileEntryIndex::None => {
-                if !self.remote_entries.is_empty() {
-                    SelectedFileEntryIndex::Remote(0)
-                } else if !self.local_entries.is_empty() {
-                    SelectedFileEntryIndex::Local(0)
+                if let Some(&i) = self.get_visible_remote_indices().first() {
+                    SelectedFileEntryIndex::Remote(i)
+                } else if let Some(&i) = self.get_visible_local_indices().first() {
+                    SelectedFileEntryIndex::Local(i)
                 } else {
                     SelectedFileEntryIndex::None
                 }
@@ -500,23 +1666,37 @@ impl FileList {
                 .highlight_symbol(">>")
         };
 
-        let title = format!("Local: {:?}", self.get_local_working_path());
-        let width = (local_rect.width - 4) as usize;
+        let title = get_title(
+            "Local",
+            self.get_local_working_path(),
+            self.sort,
+            self.local_filter.as_ref(),
+            local_rect.width.saturating_sub(2) as usize,
+        );
+        let width = local_rect.width.saturating_sub(4) as usize;
+        let columns = fit_columns(&self.columns, width);
         let items: Vec<_> = self
-            .local_entries
-            .iter()
-            .map(|entry| entry.to_text(width))
+            .get_visible_local_indices()
+            .into_iter()
+            .map(|i| self.local_entries[i].to_text(width, columns))
             .collect();
         let mut state = self.get_local_selected_index();
         let list = generate_list(&title, items.into_iter());
         frame.render_stateful_widget(list, local_rect, &mut state);
 
-        let title = format!("Remote: {:?}", self.get_remote_working_path());
-        let width = (remote_rect.width - 4) as usize;
+        let title = get_title(
+            "Remote",
+            self.get_remote_working_path(),
+            self.sort,
+            self.remote_filter.as_ref(),
+            remote_rect.width.saturating_sub(2) as usize,
+        );
+        let width = remote_rect.width.saturating_sub(4) as usize;
+        let columns = fit_columns(&self.columns, width);
         let items: Vec<_> = self
-            .remote_entries
-            .iter()
-            .map(|entry| entry.to_text(width))
+            .get_visible_remote_indices()
+            .into_iter()
+            .map(|i| self.remote_entries[i].to_text(width, columns))
             .collect();
         let mut state = self.get_remote_selected_index();
         let list = generate_list(&title, items.into_iter());
@@ -524,24 +1704,431 @@ impl FileList {
     }
 }
 
-impl PartialOrd for LocalFileEntry {
-    fn partial_cmp(&self, other: &LocalFileEntry) -> Option<std::cmp::Ordering> {
-        self.path().partial_cmp(other.path())
-    }
-}
-
-impl PartialOrd for RemoteFileEntry {
-    fn partial_cmp(&self, other: &RemoteFileEntry) -> Option<std::cmp::Ordering> {
-        self.path().partial_cmp(other.path())
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::utils::buffer_without_style;
+    use crate::checksum::HashType;
+    use crate::utils::{buffer_without_style, bytes_to_string};
+    use crossbeam_channel::unbounded;
+    use std::fs::remove_dir_all;
+    use std::io::Cursor;
+    use std::net::{Shutdown, TcpListener, TcpStream};
+    use std::time::Instant;
     use tui::{backend::TestBackend, buffer::Buffer, Terminal};
 
+    #[test]
+    fn test_partial_path() {
+        assert_eq!(
+            get_partial_path(Path::new("/a/b/movie.mkv")),
+            PathBuf::from("/a/b/.movie.mkv.rftp-part")
+        );
+        assert_eq!(
+            get_partial_path(Path::new("notes")),
+            PathBuf::from(".notes.rftp-part")
+        );
+    }
+
+    #[test]
+    fn test_resume_offset() {
+        let source: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let offset = |dest: &[u8]| {
+            resume_offset(
+                &mut Cursor::new(&source),
+                source.len() as u64,
+                &mut Cursor::new(dest),
+                dest.len() as u64,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(offset(&[]), 0);
+        assert_eq!(offset(&source[..100]), 100);
+        assert_eq!(offset(&source[..150_000]), 150_000);
+        assert_eq!(offset(&source), 200_000);
+
+        let mut corrupt = source[..150_000].to_vec();
+        corrupt[149_999] ^= 0xff;
+        assert_eq!(offset(&corrupt), 0);
+
+        let mut longer = source.clone();
+        longer.push(0);
+        assert_eq!(offset(&longer), 0);
+
+        let is_shorter = |dest: &[u8]| {
+            is_shorter_copy(
+                &mut Cursor::new(&source),
+                source.len() as u64,
+                &mut Cursor::new(dest),
+                dest.len() as u64,
+            )
+            .unwrap()
+        };
+        assert!(is_shorter(&source[..150_000]));
+        assert!(!is_shorter(&[]));
+        assert!(!is_shorter(&source));
+        assert!(!is_shorter(&corrupt));
+    }
+
+    /// Forward every connection to `listener` to `target`, delaying the data in each
+    /// direction by `latency` without limiting how much of it can be in flight.
+    fn spawn_latency_proxy(listener: TcpListener, target: String, latency: Duration) {
+        let forward = move |mut from: TcpStream, mut to: TcpStream| {
+            let (sender, receiver) = unbounded::<(Instant, Vec<u8>)>();
+            thread::spawn(move || {
+                for (due, data) in receiver {
+                    thread::sleep(due.saturating_duration_since(Instant::now()));
+                    if to.write_all(&data).is_err() {
+                        break;
+                    }
+                }
+                to.shutdown(Shutdown::Write).ok();
+            });
+            thread::spawn(move || {
+                let mut buffer = vec![0; 64 * 1024];
+                while let Ok(bytes_read) = from.read(&mut buffer) {
+                    if bytes_read == 0 {
+                        break;
+                    }
+                    let data = buffer[..bytes_read].to_vec();
+                    if sender.send((Instant::now() + latency, data)).is_err() {
+                        break;
+                    }
+                }
+            });
+        };
+        thread::spawn(move || {
+            for client in listener.incoming() {
+                let client = client.unwrap();
+                let server = TcpStream::connect(&target).unwrap();
+                forward(client.try_clone().unwrap(), server.try_clone().unwrap());
+                forward(server, client);
+            }
+        });
+    }
+
+    /// Compare the throughput of `copy()` with different numbers of requests in flight, over an
+    /// SFTP session with 10 ms of latency in each direction.
+    ///
+    /// This needs an SSH server that accepts a key from the SSH agent, e.g.,
+    /// `RFTP_BENCH_HOST=me@localhost:22 cargo test bench_copy -- --ignored --nocapture`.
+    /// `RFTP_BENCH_PATH` is the remote file that is written and read, `/tmp/rftp-bench` by default.
+    #[test]
+    #[ignore]
+    fn bench_copy() {
+        const LEN: usize = 16 * 1024 * 1024;
+        let host = env::var("RFTP_BENCH_HOST").expect("RFTP_BENCH_HOST is not set");
+        let (username, address) = host
+            .split_once('@')
+            .expect("RFTP_BENCH_HOST is not user@host");
+        let address = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{}:22", address)
+        };
+        let path = PathBuf::from(
+            env::var("RFTP_BENCH_PATH").unwrap_or_else(|_| String::from("/tmp/rftp-bench")),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_address = listener.local_addr().unwrap();
+        spawn_latency_proxy(listener, address, Duration::from_millis(10));
+        let mut session = ssh2::Session::new().unwrap();
+        session.set_tcp_stream(TcpStream::connect(proxy_address).unwrap());
+        session.handshake().unwrap();
+        session.userauth_agent(username).unwrap();
+        let sftp = session.sftp().unwrap();
+
+        let data = vec![7; LEN];
+        let report = |name: &str, elapsed: Duration| {
+            println!(
+                "{:>24}: {:>8.2?} {:>12}/s",
+                name,
+                elapsed,
+                bytes_to_string((LEN as f64 / elapsed.as_secs_f64()) as u64)
+            );
+        };
+        for &requests in &[1, 8, DEFAULT_REQUESTS] {
+            let progress = Progress::new("upload", LEN as u64);
+            let start = Instant::now();
+            copy(
+                &mut Cursor::new(&data),
+                &mut sftp.create(&path).unwrap(),
+                &progress,
+                &RateLimiter::new(0),
+                requests,
+                None,
+            )
+            .unwrap();
+            report(&format!("upload, {} requests", requests), start.elapsed());
+
+            let progress = Progress::new("download", LEN as u64);
+            let mut dest = vec![];
+            let start = Instant::now();
+            copy(
+                &mut sftp.open(&path).unwrap(),
+                &mut dest,
+                &progress,
+                &RateLimiter::new(0),
+                requests,
+                None,
+            )
+            .unwrap();
+            report(&format!("download, {} requests", requests), start.elapsed());
+            assert!(dest == data);
+        }
+        sftp.unlink(&path).unwrap();
+    }
+
+    #[test]
+    fn test_copy() {
+        let source: Vec<u8> = (0..1_000_000).map(|i| (i % 251) as u8).collect();
+        let progress = Progress::new("copy", source.len() as u64);
+        let mut dest = vec![];
+        let mut checksum = Checksum::new();
+        copy(
+            &mut Cursor::new(&source),
+            &mut dest,
+            &progress,
+            &RateLimiter::new(0),
+            3,
+            Some(&mut checksum),
+        )
+        .unwrap();
+        assert!(dest == source);
+        let mut expected_checksum = Checksum::new();
+        expected_checksum.update(&source);
+        assert_eq!(
+            checksum.hex_digest(HashType::Sha256),
+            expected_checksum.hex_digest(HashType::Sha256)
+        );
+        assert_eq!(progress.get_ratio(), 1.0);
+
+        // 200 KB at 1 MB/s should take about 200 ms.
+        let source = &source[..200_000];
+        let progress = Progress::new("limited", source.len() as u64);
+        let mut dest = vec![];
+        let start = Instant::now();
+        copy(
+            &mut Cursor::new(source),
+            &mut dest,
+            &progress,
+            &RateLimiter::new(1_000_000),
+            DEFAULT_REQUESTS,
+            None,
+        )
+        .unwrap();
+        assert!(dest == source);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_walk_local_directory() {
+        let dir = std::env::temp_dir().join(format!("rftp-walk-{}", std::process::id()));
+        create_dir_all(dir.join("a/b")).unwrap();
+        create_dir_all(dir.join("c")).unwrap();
+        for (name, len) in [("a/1", 100), ("a/b/2", 200), ("a/b/3", 300), ("4", 400)].iter() {
+            File::create(dir.join(name)).unwrap().set_len(*len).unwrap();
+        }
+
+        let entries = walk_local_directory(&dir).unwrap();
+        let paths: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.path().strip_prefix(&dir).unwrap())
+            .collect();
+        assert_eq!(paths.len(), 7);
+        // Every entry comes after the directory that contains it.
+        for (i, path) in paths.iter().enumerate() {
+            let parent = path.parent().unwrap();
+            if parent != Path::new("") {
+                let position = paths.iter().position(|p| *p == parent).unwrap();
+                assert!(position < i, "{:?} is listed before {:?}", path, parent);
+            }
+        }
+        assert_eq!(entries.iter().filter(|entry| entry.is_dir()).count(), 3);
+        assert_eq!(entries.iter().filter(|entry| entry.is_file()).count(), 4);
+
+        let progress = Progress::new("walk", 0);
+        set_progress_total(&progress, &entries);
+        progress.inc(500);
+        assert_eq!(progress.get_ratio(), 0.5);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_refresh() {
+        let dir = std::env::temp_dir().join(format!("rftp-refresh-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        for name in [".a", ".b", "c", "d"].iter() {
+            File::create(dir.join(name)).unwrap();
+        }
+        let dir = canonicalize(&dir).unwrap();
+        let mut file_list = FileList {
+            local_directory: dir.clone(),
+            remote_directory: PathBuf::new(),
+            local_entries: vec![],
+            remote_entries: vec![],
+            selected: SelectedFileEntryIndex::None,
+            sort: Sort::default(),
+            columns: vec![Column::Size],
+            local_filter: None,
+            remote_filter: None,
+        };
+        let selected_path = |file_list: &FileList| match file_list.get_selected_entry() {
+            SelectedFileEntry::Local(entry) => entry.path().to_path_buf(),
+            _ => unreachable!(),
+        };
+
+        file_list.refresh(None, false).unwrap();
+        file_list.next_selected();
+        assert_eq!(selected_path(&file_list), dir.join("c"));
+        // The selected entry moves, but stays selected.
+        file_list.refresh(None, true).unwrap();
+        assert_eq!(file_list.local_entries.len(), 5);
+        assert_eq!(selected_path(&file_list), dir.join("c"));
+
+        file_list.prev_selected();
+        assert_eq!(selected_path(&file_list), dir.join(".b"));
+        // The selected entry is hidden, so the one at the same position is selected instead.
+        file_list.refresh(None, false).unwrap();
+        assert_eq!(selected_path(&file_list), dir.join("d"));
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sort() {
+        assert_eq!(compare_names("file2", "file10"), Ordering::Less);
+        assert_eq!(compare_names("File2", "file10"), Ordering::Less);
+        assert_eq!(compare_names("b", "A"), Ordering::Greater);
+        assert_eq!(compare_names("a", "a1"), Ordering::Less);
+        assert_eq!(compare_names("v007", "v7"), Ordering::Less);
+        assert_eq!(compare_names("Notes", "notes"), Ordering::Less);
+
+        let modified_at = |seconds| FileMetadata {
+            modified: Some(seconds),
+            ..FileMetadata::default()
+        };
+        let mut entries = vec![
+            LocalFileEntry::File(PathBuf::from("/d/img10.PNG"), 300, modified_at(1)),
+            LocalFileEntry::Directory(PathBuf::from("/d/src"), modified_at(5)),
+            LocalFileEntry::File(PathBuf::from("/d/img2.png"), 100, modified_at(3)),
+            LocalFileEntry::File(PathBuf::from("/d/Makefile"), 200, FileMetadata::default()),
+            LocalFileEntry::Parent(PathBuf::from("/")),
+            LocalFileEntry::File(PathBuf::from("/d/a.txt"), 100, modified_at(2)),
+        ];
+        let mut sorted = |sort: Sort| {
+            sort.sort(&mut entries);
+            entries
+                .iter()
+                .map(|entry| entry.file_name_lossy().unwrap_or_default().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let sort = Sort::default();
+        assert_eq!(sort.to_string(), "name↑");
+        assert_eq!(
+            sorted(sort),
+            ["", "src", "a.txt", "img2.png", "img10.PNG", "Makefile"]
+        );
+        let sort = Sort {
+            directories_first: false,
+            ..sort
+        };
+        assert_eq!(sort.to_string(), "name↑ mixed");
+        assert_eq!(
+            sorted(sort),
+            ["", "a.txt", "img2.png", "img10.PNG", "Makefile", "src"]
+        );
+        let sort = Sort {
+            mode: SortMode::Size,
+            descending: true,
+            directories_first: true,
+        };
+        assert_eq!(sort.to_string(), "size↓");
+        assert_eq!(
+            sorted(sort),
+            ["", "src", "img10.PNG", "Makefile", "img2.png", "a.txt"]
+        );
+        let sort = Sort {
+            mode: SortMode::Modified,
+            descending: false,
+            directories_first: false,
+        };
+        assert_eq!(
+            sorted(sort),
+            ["", "Makefile", "img10.PNG", "a.txt", "img2.png", "src"]
+        );
+        let sort = Sort {
+            mode: SortMode::Extension,
+            ..sort
+        };
+        assert_eq!(
+            sorted(sort),
+            ["", "Makefile", "src", "img2.png", "img10.PNG", "a.txt"]
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let file =
+            |name: &str| LocalFileEntry::File(PathBuf::from(name), 1, FileMetadata::default());
+        let mut file_list = FileList {
+            local_directory: PathBuf::from("/"),
+            remote_directory: PathBuf::new(),
+            local_entries: vec![
+                LocalFileEntry::Parent(PathBuf::from("/..")),
+                file("/access.log"),
+                file("/build.rs"),
+                file("/error.log"),
+                file("/README.md"),
+            ],
+            remote_entries: vec![],
+            selected: SelectedFileEntryIndex::Local(2),
+            sort: Sort::default(),
+            columns: vec![Column::Size],
+            local_filter: None,
+            remote_filter: None,
+        };
+        let selected_path = |file_list: &FileList| file_list.get_selected_path().unwrap();
+
+        // The selected entry is hidden, so the next one that is shown is selected.
+        file_list.set_filter(Pane::Local, Some(Filter::new("*.LOG")));
+        assert_eq!(file_list.get_selected_pane(), Some(Pane::Local));
+        assert_eq!(
+            file_list.get_filter(Pane::Local),
+            Some(&Filter::new("*.LOG"))
+        );
+        assert_eq!(file_list.get_filter(Pane::Remote), None);
+        assert_eq!(selected_path(&file_list), PathBuf::from("/error.log"));
+        assert_eq!(file_list.get_local_selected_index().selected(), Some(2));
+        file_list.next_selected();
+        assert_eq!(selected_path(&file_list), PathBuf::from("/.."));
+        file_list.next_selected();
+        assert_eq!(selected_path(&file_list), PathBuf::from("/access.log"));
+        file_list.prev_selected();
+        file_list.prev_selected();
+        assert_eq!(selected_path(&file_list), PathBuf::from("/error.log"));
+
+        assert!(file_list.select_match(&Filter::new("acc"), false, false));
+        assert_eq!(selected_path(&file_list), PathBuf::from("/access.log"));
+        assert!(file_list.select_match(&Filter::new("log"), false, false));
+        assert_eq!(selected_path(&file_list), PathBuf::from("/error.log"));
+        assert!(file_list.select_match(&Filter::new("log"), false, true));
+        assert_eq!(selected_path(&file_list), PathBuf::from("/access.log"));
+        assert!(file_list.select_match(&Filter::new("log"), true, false));
+        assert_eq!(selected_path(&file_list), PathBuf::from("/access.log"));
+        // Hidden entries are not searched.
+        assert!(!file_list.select_match(&Filter::new("readme"), false, false));
+
+        file_list.set_filter(Pane::Local, Some(Filter::new("nothing")));
+        assert_eq!(selected_path(&file_list), PathBuf::from("/.."));
+        file_list.local_entries.remove(0);
+        file_list.set_filter(Pane::Local, Some(Filter::new("nothing")));
+        assert_eq!(file_list.get_selected_pane(), None);
+        file_list.set_filter(Pane::Local, None);
+        assert!(file_list.select_match(&Filter::new("readme"), false, false));
+        assert_eq!(selected_path(&file_list), PathBuf::from("/README.md"));
+    }
+
     #[test]
     fn test_file_list() {
         let file_list: FileList = FileList {
@@ -549,18 +2136,47 @@ mod tests {
             remote_directory: PathBuf::from("home/files"),
             local_entries: vec![
                 LocalFileEntry::Parent(PathBuf::from("/a/b/c/..")),
-                LocalFileEntry::File(PathBuf::from("/a/b/c/myfile.txt"), 30_000),
-                LocalFileEntry::File(PathBuf::from("/a/b/c/myotherfile.dat"), 128),
-                LocalFileEntry::Directory(PathBuf::from("/a/b/c/important")),
+                LocalFileEntry::File(
+                    PathBuf::from("/a/b/c/myfile.txt"),
+                    30_000,
+                    FileMetadata::default(),
+                ),
+                LocalFileEntry::File(
+                    PathBuf::from("/a/b/c/myotherfile.dat"),
+                    128,
+                    FileMetadata::default(),
+                ),
+                LocalFileEntry::Directory(
+                    PathBuf::from("/a/b/c/important"),
+                    FileMetadata::default(),
+                ),
             ],
             remote_entries: vec![
                 RemoteFileEntry::Parent(PathBuf::from("home/files/..")),
-                RemoteFileEntry::File(PathBuf::from("home/files/pic.png"), 55_000),
-                RemoteFileEntry::File(PathBuf::from("home/files/movie.mkv"), 123_000_000),
-                RemoteFileEntry::Directory(PathBuf::from("home/files/games")),
-                RemoteFileEntry::Directory(PathBuf::from("home/files/trash")),
+                RemoteFileEntry::File(
+                    PathBuf::from("home/files/pic.png"),
+                    55_000,
+                    FileMetadata::default(),
+                ),
+                RemoteFileEntry::File(
+                    PathBuf::from("home/files/movie.mkv"),
+                    123_000_000,
+                    FileMetadata::default(),
+                ),
+                RemoteFileEntry::Directory(
+                    PathBuf::from("home/files/games"),
+                    FileMetadata::default(),
+                ),
+                RemoteFileEntry::Directory(
+                    PathBuf::from("home/files/trash"),
+                    FileMetadata::default(),
+                ),
             ],
             selected: SelectedFileEntryIndex::Remote(2),
+            sort: Sort::default(),
+            columns: vec![Column::Size],
+            local_filter: None,
+            remote_filter: None,
         };
 
         let mut terminal = Terminal::new(TestBackend::new(50, 8)).unwrap();
@@ -575,7 +2191,7 @@ mod tests {
         assert_eq!(
             buffer_without_style(terminal.backend().buffer()),
             Buffer::with_lines(vec![
-                "┌Local: \"/a/b/c\"────────┐┌Remote: \"home/files\"───┐",
+                "┌Local: \"/a/b/c\" [name↑]┐┌Remote: \"home/files\"───┐",
                 "│⬅                      ││  ⬅                    │",
                 "│myfile.txt    30.0 KB  ││  pic.png       55.0 KB│",
                 "│myotherfile.dat 128 B  ││>>movie.mkv    123.0 MB│",
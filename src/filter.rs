@@ -0,0 +1,60 @@
+use crate::ssh_config::matches_wildcard;
+
+use std::fmt;
+
+/// A pattern that file names are matched against, ignoring case.
+///
+/// A pattern with a `*` or `?` is a glob that must match the whole name, e.g., `*.log`. Any
+/// other pattern is fuzzy and matches the names that contain its characters in order, e.g.,
+/// `rdme` matches `README.md`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+    pattern: String,
+}
+
+impl Filter {
+    pub fn new(pattern: &str) -> Self {
+        Filter {
+            pattern: pattern.to_string(),
+        }
+    }
+
+    /// Return `true` if the file name `name` matches this pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        let pattern = self.pattern.to_lowercase();
+        if pattern.contains(['*', '?']) {
+            matches_wildcard(&name, &pattern)
+        } else {
+            let mut name = name.chars();
+            pattern.chars().all(|c| name.any(|n| n == c))
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter::new("rdme");
+        assert!(filter.matches("README.md"));
+        assert!(filter.matches("readme"));
+        assert!(!filter.matches("dream"));
+
+        let filter = Filter::new("*.LOG");
+        assert!(filter.matches("syslog.log"));
+        assert!(!filter.matches("syslog.log.1"));
+        assert!(Filter::new("access.?").matches("access.1"));
+
+        assert!(Filter::new("").matches("anything"));
+        assert_eq!(Filter::new("*.rs").to_string(), "*.rs");
+    }
+}
@@ -0,0 +1,383 @@
+use crate::ssh_config::matches_pattern_list;
+
+use hmac::{Hmac, Mac, NewMac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{create_dir_all, read, read_to_string, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The port of entries whose host name has no port.
+const DEFAULT_PORT: u16 = 22;
+/// The prefix of a hashed host name.
+const HASH_MAGIC: &str = "|1|";
+/// The length of the salt of a hashed host name, the same as a SHA-1 digest.
+const SALT_LEN: usize = 20;
+
+/// What to do with a host key that is not known, the same as OpenSSH's
+/// `StrictHostKeyChecking`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrictHostKeyChecking {
+    /// Ask the user whether to add an unknown key.
+    Ask,
+    /// Never connect to a host whose key is not known.
+    Yes,
+    /// Add unknown keys without asking.
+    AcceptNew,
+    /// Add unknown keys without asking, and connect even if the key has changed, but without
+    /// sending a password.
+    No,
+}
+
+impl FromStr for StrictHostKeyChecking {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ask" => Ok(StrictHostKeyChecking::Ask),
+            "yes" => Ok(StrictHostKeyChecking::Yes),
+            "accept-new" => Ok(StrictHostKeyChecking::AcceptNew),
+            "no" | "off" => Ok(StrictHostKeyChecking::No),
+            _ => Err(format!("unknown host key checking mode \"{}\"", s)),
+        }
+    }
+}
+
+/// What kind of key a line of a known hosts file has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Marker {
+    HostKey,
+    /// A key that must not be accepted.
+    Revoked,
+}
+
+/// A key from a line of a known hosts file.
+struct Entry {
+    location: Location,
+    marker: Marker,
+    /// The comma-separated host name patterns, or a single hashed host name.
+    hosts: String,
+    key_type: String,
+    key: Vec<u8>,
+}
+
+impl Entry {
+    /// Return `true` if this entry applies to `name`, e.g., "example.com" or
+    /// "[example.com]:2222".
+    fn matches_host(&self, name: &str) -> bool {
+        match self.hosts.strip_prefix(HASH_MAGIC) {
+            Some(hashed) => {
+                let mut parts = hashed.splitn(2, '|');
+                let salt = parts.next().and_then(|salt| base64::decode(salt).ok());
+                let hash = parts.next().and_then(|hash| base64::decode(hash).ok());
+                match (salt, hash) {
+                    (Some(salt), Some(hash)) => hash_host_name(name, &salt) == hash,
+                    _ => false,
+                }
+            }
+            None => matches_pattern_list(name, self.hosts.split(',')),
+        }
+    }
+}
+
+/// A line of a known hosts file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    /// The line number, starting at 1.
+    pub line: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)
+    }
+}
+
+/// The result of looking up the key of a host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    /// The key is known for the host.
+    Match(Location),
+    /// The key has been revoked.
+    Revoked(Location),
+    /// Only a different key of the same type is known for the host.
+    Mismatch(Location),
+    NotFound,
+}
+
+/// The keys from one or more OpenSSH known hosts files.
+pub struct KnownHosts {
+    entries: Vec<Entry>,
+}
+
+impl KnownHosts {
+    /// Read the keys from the files at `paths`, skipping the ones that do not exist.
+    ///
+    /// Lines that cannot be parsed are skipped, like OpenSSH does.
+    pub fn load<'a>(paths: impl IntoIterator<Item = &'a PathBuf>) -> io::Result<Self> {
+        let mut entries = vec![];
+        for path in paths {
+            let contents = match read_to_string(path) {
+                Ok(contents) => contents,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            entries.extend(
+                contents
+                    .lines()
+                    .enumerate()
+                    .filter_map(|(i, line)| parse_line(line, path, i + 1)),
+            );
+        }
+        Ok(KnownHosts { entries })
+    }
+
+    /// Look up `key`, the host key of `host` on `port` in the SSH wire format.
+    ///
+    /// A revoked key is never accepted, and a key that is known for the host is accepted even
+    /// if other keys of its type are too.
+    pub fn check(&self, host: &str, port: u16, key: &[u8]) -> HostKeyStatus {
+        let name = get_host_name(host, port);
+        let key_type = get_key_type(key);
+        let mut found = None;
+        let mut mismatch = None;
+        for entry in self
+            .entries
+            .iter()
+            .filter(|entry| entry.matches_host(&name))
+        {
+            let is_same_key = entry.key == key;
+            match entry.marker {
+                Marker::Revoked if is_same_key => {
+                    return HostKeyStatus::Revoked(entry.location.clone())
+                }
+                Marker::HostKey if is_same_key => {
+                    found.get_or_insert_with(|| entry.location.clone());
+                }
+                Marker::HostKey if key_type == Some(entry.key_type.as_str()) => {
+                    mismatch.get_or_insert_with(|| entry.location.clone());
+                }
+                _ => {}
+            }
+        }
+        match (found, mismatch) {
+            (Some(location), _) => HostKeyStatus::Match(location),
+            (None, Some(location)) => HostKeyStatus::Mismatch(location),
+            (None, None) => HostKeyStatus::NotFound,
+        }
+    }
+}
+
+/// Append `key`, the host key of `host` on `port` in the SSH wire format, to the known hosts
+/// file at `path`, which is created if needed.
+///
+/// If `hash` is set, the host name is hashed like `HashKnownHosts` does.
+pub fn add_host_key(path: &Path, host: &str, port: u16, key: &[u8], hash: bool) -> io::Result<()> {
+    let key_type = get_key_type(key)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid host key"))?;
+    let name = get_host_name(host, port);
+    let name = if hash {
+        let mut salt = [0; SALT_LEN];
+        getrandom::getrandom(&mut salt).map_err(|err| io::Error::other(err.to_string()))?;
+        format!(
+            "{}{}|{}",
+            HASH_MAGIC,
+            base64::encode(salt),
+            base64::encode(hash_host_name(&name, &salt))
+        )
+    } else {
+        name
+    };
+
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+    // The new entry must not be joined to a last line without a newline.
+    let needs_newline = match read(path) {
+        Ok(contents) => !contents.is_empty() && !contents.ends_with(b"\n"),
+        Err(_) => false,
+    };
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(
+        file,
+        "{}{} {} {}",
+        if needs_newline { "\n" } else { "" },
+        name,
+        key_type,
+        base64::encode(key)
+    )
+}
+
+/// Return the name that a known hosts file uses for `host` on `port`.
+fn get_host_name(host: &str, port: u16) -> String {
+    if port == DEFAULT_PORT {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// Return the SHA256 fingerprint of `key` in the format that OpenSSH shows.
+pub fn get_fingerprint(key: &[u8]) -> String {
+    let hash = base64::encode(Sha256::digest(key));
+    format!("SHA256:{}", hash.trim_end_matches('='))
+}
+
+/// Return the type of `key`, e.g., "ssh-ed25519", which is the first field of the SSH wire
+/// format.
+pub fn get_key_type(key: &[u8]) -> Option<&str> {
+    let len = key.get(..4)?;
+    let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    std::str::from_utf8(key.get(4..4 + len)?).ok()
+}
+
+/// Return the HMAC-SHA1 of `name` with `salt` as the key.
+fn hash_host_name(name: &str, salt: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_varkey(salt).expect("HMAC accepts keys of any length");
+    mac.update(name.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Parse a line of the form `[marker] hosts key-type key [comment]`, or return `None` if it is
+/// empty, a comment, or invalid.
+///
+/// `@cert-authority` lines are left out like other unknown markers, since libssh2 cannot
+/// negotiate host certificates, so there is never a certificate to check against them.
+fn parse_line(line: &str, path: &Path, number: usize) -> Option<Entry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let mut fields = line.split_whitespace();
+    let first = fields.next()?;
+    let (marker, hosts) = match first {
+        "@revoked" => (Marker::Revoked, fields.next()?),
+        _ if first.starts_with('@') => return None,
+        _ => (Marker::HostKey, first),
+    };
+    let key_type = fields.next()?;
+    let key = base64::decode(fields.next()?).ok()?;
+    Some(Entry {
+        location: Location {
+            path: path.to_path_buf(),
+            line: number,
+        },
+        marker,
+        hosts: hosts.to_string(),
+        key_type: key_type.to_string(),
+        key,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{remove_dir_all, write};
+
+    /// Return a fake key of `key_type` in the SSH wire format.
+    fn make_key(key_type: &str, fill: u8) -> Vec<u8> {
+        let mut key = (key_type.len() as u32).to_be_bytes().to_vec();
+        key.extend_from_slice(key_type.as_bytes());
+        key.extend_from_slice(&[fill; 32]);
+        key
+    }
+
+    #[test]
+    fn test_known_hosts() {
+        let dir = std::env::temp_dir().join(format!("rftp-known-hosts-{}", std::process::id()));
+        let path = dir.join("known_hosts");
+        let key = |fill| base64::encode(make_key("ssh-ed25519", fill));
+        let salt = [7; SALT_LEN];
+        let hashed = format!(
+            "|1|{}|{}",
+            base64::encode(salt),
+            base64::encode(hash_host_name("[hashed.example.com]:2222", &salt))
+        );
+        create_dir_all(&dir).unwrap();
+        write(
+            &path,
+            format!(
+                "# A comment\n\
+                 web.example.com,10.0.0.1 ssh-ed25519 {}\n\
+                 [web.example.com]:2222 ssh-ed25519 {}\n\
+                 {} ssh-ed25519 {} comment\n\
+                 @revoked * ssh-ed25519 {}\n\
+                 @cert-authority *.example.com ssh-ed25519 {}\n\
+                 not a valid line",
+                key(1),
+                key(2),
+                hashed,
+                key(3),
+                key(4),
+                key(5)
+            ),
+        )
+        .unwrap();
+        let known_hosts = KnownHosts::load(&[path.clone(), dir.join("missing")]).unwrap();
+        let location = |line| Location {
+            path: path.clone(),
+            line,
+        };
+
+        let check =
+            |host, port, fill| known_hosts.check(host, port, &make_key("ssh-ed25519", fill));
+        assert_eq!(
+            check("web.example.com", 22, 1),
+            HostKeyStatus::Match(location(2))
+        );
+        assert_eq!(check("10.0.0.1", 22, 1), HostKeyStatus::Match(location(2)));
+        assert_eq!(
+            check("web.example.com", 2222, 2),
+            HostKeyStatus::Match(location(3))
+        );
+        assert_eq!(
+            check("web.example.com", 2222, 1),
+            HostKeyStatus::Mismatch(location(3))
+        );
+        assert_eq!(
+            check("hashed.example.com", 2222, 3),
+            HostKeyStatus::Match(location(4))
+        );
+        assert_eq!(check("hashed.example.com", 22, 3), HostKeyStatus::NotFound);
+        assert_eq!(
+            check("web.example.com", 22, 4),
+            HostKeyStatus::Revoked(location(5))
+        );
+        assert_eq!(check("db.example.com", 22, 5), HostKeyStatus::NotFound);
+        assert_eq!(
+            known_hosts.check("web.example.com", 22, &make_key("ssh-rsa", 1)),
+            HostKeyStatus::NotFound
+        );
+
+        let new_key = make_key("ssh-ed25519", 6);
+        add_host_key(&path, "db.example.com", 2200, &new_key, false).unwrap();
+        add_host_key(&path, "secret.example.com", 22, &new_key, true).unwrap();
+        let contents = read_to_string(&path).unwrap();
+        assert!(contents.contains(&format!(
+            "not a valid line\n[db.example.com]:2200 ssh-ed25519 {}\n|1|",
+            base64::encode(&new_key)
+        )));
+        let known_hosts = KnownHosts::load(std::iter::once(&path)).unwrap();
+        assert_eq!(
+            known_hosts.check("db.example.com", 2200, &new_key),
+            HostKeyStatus::Match(location(8))
+        );
+        assert_eq!(
+            known_hosts.check("secret.example.com", 22, &new_key),
+            HostKeyStatus::Match(location(9))
+        );
+        assert!(!contents.contains("secret.example.com"));
+        assert_eq!(
+            get_fingerprint(b""),
+            "SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU"
+        );
+        assert_eq!(
+            "accept-new".parse::<StrictHostKeyChecking>(),
+            Ok(StrictHostKeyChecking::AcceptNew)
+        );
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,11 +3,24 @@ extern crate crossbeam_channel;
 #[macro_use]
 extern crate clap;
 
+mod checksum;
+mod column;
+mod completion;
+mod conflict;
 mod connect;
+mod destination;
 mod events;
 mod file;
+mod filter;
+mod known_hosts;
+mod preferences;
 mod progress;
+mod prompt;
+mod queue;
+mod rate_limit;
+mod retry;
 mod rftp;
+mod ssh_config;
 mod user_message;
 mod utils;
 
@@ -0,0 +1,119 @@
+use dirs::config_dir;
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io;
+use std::path::PathBuf;
+
+/// Settings that are remembered for each host between runs, stored in `rftp/hosts` in the
+/// user's configuration directory.
+///
+/// Each line of the file is a host followed by its settings as `key=value` pairs.
+pub struct Preferences {
+    path: Option<PathBuf>,
+    hosts: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Preferences {
+    /// Read the saved settings, or start without any if they cannot be read.
+    pub fn load() -> Self {
+        let path = config_dir().map(|dir| dir.join("rftp").join("hosts"));
+        let contents = path
+            .as_ref()
+            .and_then(|path| read_to_string(path).ok())
+            .unwrap_or_default();
+        Preferences {
+            path,
+            hosts: parse(&contents),
+        }
+    }
+
+    /// Return the value of `key` for `host`, if one was saved.
+    pub fn get(&self, host: &str, key: &str) -> Option<&str> {
+        self.hosts
+            .get(host)
+            .and_then(|settings| settings.get(key))
+            .map(String::as_str)
+    }
+
+    /// Set the value of `key` for `host`, which is only remembered once `save()` is called.
+    pub fn set(&mut self, host: &str, key: &str, value: &str) {
+        self.hosts
+            .entry(host.to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+    }
+
+    /// Write the settings of every host to the file.
+    pub fn save(&self) -> io::Result<()> {
+        let path = self.path.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "unable to find configuration directory",
+            )
+        })?;
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        write(path, format(&self.hosts))
+    }
+}
+
+/// Parse the lines of a preferences file, skipping the ones that are not valid.
+fn parse(contents: &str) -> BTreeMap<String, BTreeMap<String, String>> {
+    let mut hosts = BTreeMap::new();
+    for line in contents.lines() {
+        let mut fields = line.split_whitespace();
+        let host = match fields.next() {
+            Some(host) if !host.starts_with('#') => host,
+            _ => continue,
+        };
+        let settings: &mut BTreeMap<_, _> = hosts.entry(host.to_string()).or_default();
+        for field in fields {
+            if let Some(i) = field.find('=') {
+                settings.insert(field[..i].to_string(), field[i + 1..].to_string());
+            }
+        }
+    }
+    hosts
+}
+
+/// Return the contents of a preferences file with the settings of `hosts`.
+fn format(hosts: &BTreeMap<String, BTreeMap<String, String>>) -> String {
+    let mut contents = String::from("# Settings that rftp remembers for each host.\n");
+    for (host, settings) in hosts {
+        contents.push_str(host);
+        for (key, value) in settings {
+            contents.push_str(&format!(" {}={}", key, value));
+        }
+        contents.push('\n');
+    }
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preferences() {
+        let contents = "# A comment\nweb show_hidden_files=true\n\ndb invalid x=1\n";
+        let mut preferences = Preferences {
+            path: None,
+            hosts: parse(contents),
+        };
+        assert_eq!(preferences.get("web", "show_hidden_files"), Some("true"));
+        assert_eq!(preferences.get("db", "x"), Some("1"));
+        assert_eq!(preferences.get("db", "invalid"), None);
+        assert_eq!(preferences.get("other", "x"), None);
+
+        preferences.set("db", "show_hidden_files", "false");
+        assert_eq!(
+            format(&preferences.hosts),
+            "# Settings that rftp remembers for each host.\n\
+             db show_hidden_files=false x=1\n\
+             web show_hidden_files=true\n"
+        );
+        assert_eq!(parse(&format(&preferences.hosts)), preferences.hosts);
+        assert!(preferences.save().is_err());
+    }
+}
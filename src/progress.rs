@@ -1,7 +1,8 @@
 use crate::utils::{bitrate_to_string, bytes_to_string, duration_to_string};
 
 use std::collections::VecDeque;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
 use tui::{
@@ -14,11 +15,59 @@ use tui::{
 const HISTORY_MAX_AGE: Duration = Duration::from_secs(5);
 const PROGRESSBAR_COLOR: Color = Color::LightBlue;
 
+/// The state of the transfer that a progress bar is tracking.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressState {
+    Running,
+    Paused,
+    Finished,
+    Cancelled,
+    Failed,
+}
+
+impl ProgressState {
+    fn from_u8(state: u8) -> Self {
+        match state {
+            0 => ProgressState::Running,
+            1 => ProgressState::Paused,
+            2 => ProgressState::Finished,
+            3 => ProgressState::Cancelled,
+            4 => ProgressState::Failed,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return `true` if a transfer in this state will not make any more progress.
+    pub fn is_terminal(self) -> bool {
+        match self {
+            ProgressState::Running | ProgressState::Paused => false,
+            ProgressState::Finished | ProgressState::Cancelled | ProgressState::Failed => true,
+        }
+    }
+}
+
+impl fmt::Display for ProgressState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ProgressState::Running => "running",
+            ProgressState::Paused => "paused",
+            ProgressState::Finished => "done",
+            ProgressState::Cancelled => "cancelled",
+            ProgressState::Failed => "failed",
+        };
+        f.pad(name)
+    }
+}
+
 pub struct Progress {
     title: String,
     bytes_sent: AtomicU64,
-    total_bytes: u64,
-    is_finished: AtomicBool,
+    total_bytes: AtomicU64,
+    files_sent: AtomicU64,
+    total_files: AtomicU64,
+    state: AtomicU8,
+    attempt: AtomicU32,
+    max_attempts: AtomicU32,
     history: Mutex<VecDeque<(Instant, u64)>>,
 }
 
@@ -29,34 +78,127 @@ impl Progress {
         Progress {
             title: title.to_string(),
             bytes_sent: AtomicU64::new(0),
-            total_bytes,
-            is_finished: AtomicBool::new(false),
+            total_bytes: AtomicU64::new(total_bytes),
+            files_sent: AtomicU64::new(0),
+            total_files: AtomicU64::new(1),
+            state: AtomicU8::new(ProgressState::Running as u8),
+            attempt: AtomicU32::new(1),
+            max_attempts: AtomicU32::new(1),
             history,
         }
     }
 
-    /// Return the title of this progress bar.
-    pub fn get_title(&self) -> &str {
-        self.title.as_str()
+    /// Return the title of this progress bar, and the attempt number if it has been retried.
+    pub fn get_title(&self) -> String {
+        let attempt = self.attempt.load(Ordering::Relaxed);
+        if attempt > 1 {
+            format!(
+                "{} (attempt {}/{})",
+                self.title,
+                attempt,
+                self.max_attempts.load(Ordering::Relaxed)
+            )
+        } else {
+            self.title.clone()
+        }
     }
 
     /// Return the fraction that this progress bar has completed.
     pub fn get_ratio(&self) -> f64 {
-        if self.total_bytes == 0 {
+        let total_bytes = self.total_bytes.load(Ordering::Relaxed);
+        if total_bytes == 0 {
             0.0
         } else {
             let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
-            if bytes_sent >= self.total_bytes {
+            if bytes_sent >= total_bytes {
                 1.0
             } else {
-                (bytes_sent as f64) / (self.total_bytes as f64)
+                (bytes_sent as f64) / (total_bytes as f64)
             }
         }
     }
 
-    /// Return `true` if this progress bar is finished.
+    /// Set the total number of bytes and files this progress bar is tracking.
+    ///
+    /// This is used when the size of a transfer is only known after it has started,
+    /// e.g., when walking a directory tree.
+    pub fn set_total(&self, total_bytes: u64, total_files: u64) {
+        self.total_bytes.store(total_bytes, Ordering::Relaxed);
+        self.total_files.store(total_files, Ordering::Relaxed);
+    }
+
+    /// Return the state of the transfer this progress bar is tracking.
+    pub fn get_state(&self) -> ProgressState {
+        ProgressState::from_u8(self.state.load(Ordering::Relaxed))
+    }
+
+    /// Return `true` if this progress bar is finished, cancelled, or failed.
     pub fn is_finished(&self) -> bool {
-        self.is_finished.load(Ordering::Relaxed)
+        self.get_state().is_terminal()
+    }
+
+    /// Return `true` if this progress bar has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.get_state() == ProgressState::Cancelled
+    }
+
+    /// Return `true` if this progress bar has been paused.
+    pub fn is_paused(&self) -> bool {
+        self.get_state() == ProgressState::Paused
+    }
+
+    /// Move from the state `from` to the state `to`.
+    ///
+    /// Return `false` if the progress bar was not in any of the states in `from`.
+    fn transition(&self, from: &[ProgressState], to: ProgressState) -> bool {
+        self.state
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |state| {
+                if from.contains(&ProgressState::from_u8(state)) {
+                    Some(to as u8)
+                } else {
+                    None
+                }
+            })
+            .is_ok()
+    }
+
+    /// Ask the transfer to pause. Return `false` if it is not running.
+    pub fn pause(&self) -> bool {
+        self.transition(&[ProgressState::Running], ProgressState::Paused)
+    }
+
+    /// Ask a paused transfer to continue. Return `false` if it is not paused.
+    pub fn resume(&self) -> bool {
+        self.transition(&[ProgressState::Paused], ProgressState::Running)
+    }
+
+    /// Ask the transfer to stop. Return `false` if it has already stopped.
+    pub fn cancel(&self) -> bool {
+        self.transition(
+            &[ProgressState::Running, ProgressState::Paused],
+            ProgressState::Cancelled,
+        )
+    }
+
+    /// Mark the transfer as failed unless it has already stopped.
+    pub fn fail(&self) {
+        self.history.lock().unwrap().clear();
+        self.transition(
+            &[ProgressState::Running, ProgressState::Paused],
+            ProgressState::Failed,
+        );
+    }
+
+    /// Start over for the attempt number `attempt` out of `max_attempts`.
+    ///
+    /// Any bytes the last attempt sent that are still at the destination should be counted
+    /// again with `skip()`.
+    pub fn restart(&self, attempt: u32, max_attempts: u32) {
+        self.bytes_sent.store(0, Ordering::Relaxed);
+        self.files_sent.store(0, Ordering::Relaxed);
+        self.attempt.store(attempt, Ordering::Relaxed);
+        self.max_attempts.store(max_attempts, Ordering::Relaxed);
+        *self.history.lock().unwrap() = vec![(Instant::now(), 0)].into_iter().collect();
     }
 
     /// Tell the progress bar how many `bytes` have been successfully sent.
@@ -80,29 +222,45 @@ impl Progress {
         self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
     }
 
+    /// Tell the progress bar that `bytes` were already present at the destination.
+    ///
+    /// Unlike `inc()`, these bytes do not count towards the bitrate.
+    pub fn skip(&self, bytes: u64) {
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Tell the progress bar that one more file has been completely sent.
+    pub fn inc_files(&self) {
+        self.files_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Finish this progress bar.
     pub fn finish(&self) {
-        self.bytes_sent.store(self.total_bytes, Ordering::Relaxed);
+        self.bytes_sent
+            .store(self.total_bytes.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.files_sent
+            .store(self.total_files.load(Ordering::Relaxed), Ordering::Relaxed);
         self.history.lock().unwrap().clear();
-        self.is_finished.store(true, Ordering::Relaxed);
+        self.state
+            .store(ProgressState::Finished as u8, Ordering::Relaxed);
     }
 
     /// Return the current estimated number of bits sent per second.
     ///
-    /// This value is computed over the last 5 seconds.
+    /// This value is computed over the last 5 seconds, up to now, so that the rate drops
+    /// while a transfer is stalled, e.g., by a rate limit.
     pub fn get_current_bitrate(&self) -> u64 {
         let history = self.history.lock().unwrap();
-        let instants = history.iter().map(|(t, _)| t);
-        let bits_sent = 8 * history.iter().map(|(_, b)| b).sum::<u64>();
-        let seconds = instants
-            .clone()
-            .min()
-            .and_then(|oldest| {
-                instants
-                    .max()
-                    .map(|yongest| yongest.duration_since(*oldest).as_secs_f64())
-            })
-            .unwrap_or(0.0);
+        let (oldest, youngest) = match (history.front(), history.back()) {
+            (Some((oldest, _)), Some((youngest, _))) => (*oldest, *youngest),
+            _ => return 0,
+        };
+        // The bytes of the oldest item were sent before the period we measure.
+        let bits_sent = 8 * history.iter().skip(1).map(|(_, b)| b).sum::<u64>();
+        let seconds = Instant::now()
+            .max(youngest)
+            .duration_since(oldest)
+            .as_secs_f64();
         if seconds == 0.0 {
             0
         } else {
@@ -120,8 +278,9 @@ impl Progress {
                 None
             } else {
                 let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
-                if bytes_sent <= self.total_bytes {
-                    let remaining_bytes = self.total_bytes - bytes_sent;
+                let total_bytes = self.total_bytes.load(Ordering::Relaxed);
+                if bytes_sent <= total_bytes {
+                    let remaining_bytes = total_bytes - bytes_sent;
                     Some(Duration::from_secs_f64(
                         remaining_bytes as f64 / bytes_per_second as f64,
                     ))
@@ -158,8 +317,7 @@ impl Progress {
 
             let rects = Layout::default()
                 .constraints(
-                    std::iter::repeat(Constraint::Length(1))
-                        .take(progress_bars.len())
+                    std::iter::repeat_n(Constraint::Length(1), progress_bars.len())
                         .collect::<Vec<Constraint>>(),
                 )
                 .split(progress_rect);
@@ -178,16 +336,31 @@ impl Progress {
         B: tui::backend::Backend,
     {
         let bitrate = self.get_current_bitrate();
-        let eta = self
-            .get_eta()
-            .map(|eta| duration_to_string(eta))
-            .unwrap_or("??:??".to_string());
+        let status = match self.get_state() {
+            ProgressState::Running | ProgressState::Finished => format!(
+                "{} ETA",
+                self.get_eta()
+                    .map(duration_to_string)
+                    .unwrap_or("??:??".to_string())
+            ),
+            state => state.to_string(),
+        };
+        let total_files = self.total_files.load(Ordering::Relaxed);
         let info = format!(
-            "{}/{}  {}  {} ETA",
+            "{}{}/{}  {}  {}",
+            if total_files > 1 {
+                format!(
+                    "{}/{} files  ",
+                    self.files_sent.load(Ordering::Relaxed),
+                    total_files
+                )
+            } else {
+                String::new()
+            },
             bytes_to_string(self.bytes_sent.load(Ordering::Relaxed)),
-            bytes_to_string(self.total_bytes),
+            bytes_to_string(self.total_bytes.load(Ordering::Relaxed)),
             bitrate_to_string(bitrate),
-            eta
+            status
         );
         let width = frame.size().width as usize;
         let label = if info.len() + 5 >= width {
@@ -233,13 +406,26 @@ mod tests {
                 let finished = Progress::new("finished.jpg", 100);
                 finished.inc(50);
                 finished.finish();
+                let directory = Progress::new("build", 0);
+                directory.set_total(2000, 12);
+                directory.bytes_sent.store(500, Ordering::Relaxed);
+                directory.inc_files();
+                let paused = Progress::new("paused.iso", 100);
+                paused.pause();
+                let retried = Progress::new("retried.bin", 100);
+                retried.inc(50);
+                retried.restart(2, 4);
                 let with_history = {
                     let now = Instant::now();
                     Progress {
                         title: "with_history.dat".into(),
                         bytes_sent: AtomicU64::new(0),
-                        total_bytes: 1e6 as u64,
-                        is_finished: AtomicBool::new(false),
+                        total_bytes: AtomicU64::new(1e6 as u64),
+                        files_sent: AtomicU64::new(0),
+                        total_files: AtomicU64::new(1),
+                        state: AtomicU8::new(ProgressState::Running as u8),
+                        attempt: AtomicU32::new(1),
+                        max_attempts: AtomicU32::new(1),
                         history: Mutex::new(
                             vec![
                                 (now, 0),
@@ -254,7 +440,15 @@ mod tests {
                     }
                 };
                 Progress::draw_progress_bars(
-                    vec![&just_started, &long, &with_history, &finished],
+                    vec![
+                        &just_started,
+                        &long,
+                        &with_history,
+                        &finished,
+                        &directory,
+                        &paused,
+                        &retried,
+                    ],
                     &mut frame,
                     rect,
                 );
@@ -264,14 +458,14 @@ mod tests {
         assert_eq!(
             buffer_without_style(terminal.backend().buffer()),
             Buffer::with_lines(vec![
-                "                                                            ",
-                "                                                            ",
-                "                                                            ",
                 "                                                            ",
                 "just_started.txt               0 B/100 B  0 bit/s  ??:?? ETA",
                 "this_is_a_really_long_filename 0 B/100 B  0 bit/s  ??:?? ETA",
                 "with_history.dat         0 B/1.0 MB  131.1 Kbit/s  01:01 ETA",
                 "finished.jpg                 100 B/100 B  0 bit/s  00:00 ETA",
+                "build           1/12 files  500 B/2.0 KB  0 bit/s  ??:?? ETA",
+                "paused.iso                        0 B/100 B  0 bit/s  paused",
+                "retried.bin (attempt 2/4)      0 B/100 B  0 bit/s  ??:?? ETA",
             ])
         );
     }
@@ -0,0 +1,170 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    widgets::{Paragraph, Text},
+};
+
+const PROMPT_LABEL_COLOR: Color = Color::Yellow;
+const PROMPT_CURSOR_COLOR: Color = Color::White;
+
+/// What a key press did to a prompt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PromptEvent {
+    /// The text was edited.
+    Changed,
+    /// The user pressed Enter.
+    Submitted,
+    /// The user pressed Esc.
+    Cancelled,
+    /// The user pressed Tab to complete the text.
+    Completed,
+    /// The key has no meaning in a prompt.
+    Ignored,
+}
+
+/// A line of text that the user types at the bottom of the screen, e.g., a search.
+pub struct Prompt {
+    label: String,
+    text: String,
+}
+
+impl Prompt {
+    /// Return a prompt that shows `label` before the text, which starts as `text`.
+    pub fn new(label: &str, text: &str) -> Self {
+        Prompt {
+            label: label.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    /// Return the text that has been typed.
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replace the text that has been typed, e.g., with a completion.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+    }
+
+    /// Edit the text with `key`.
+    ///
+    /// Backspace deletes a character, Ctrl-W deletes a word and Ctrl-U deletes everything.
+    pub fn on_event(&mut self, key: KeyEvent) -> PromptEvent {
+        match (key.code, key.modifiers) {
+            (KeyCode::Enter, _) => PromptEvent::Submitted,
+            (KeyCode::Esc, _) => PromptEvent::Cancelled,
+            (KeyCode::Tab, _) => PromptEvent::Completed,
+            (KeyCode::Backspace, _) => self.edit(|text| {
+                text.pop();
+            }),
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => self.edit(String::clear),
+            (KeyCode::Char('w'), KeyModifiers::CONTROL) => self.edit(|text| {
+                let word = text.trim_end_matches(['/', ' ']);
+                let end = word.rfind(['/', ' ']).map_or(0, |i| i + 1);
+                text.truncate(end);
+            }),
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.edit(|text| text.push(c))
+            }
+            _ => PromptEvent::Ignored,
+        }
+    }
+
+    /// Apply `f` to the text and return whether it changed.
+    fn edit<F: FnOnce(&mut String)>(&mut self, f: F) -> PromptEvent {
+        let old_len = self.text.len();
+        f(&mut self.text);
+        if self.text.len() != old_len {
+            PromptEvent::Changed
+        } else {
+            PromptEvent::Ignored
+        }
+    }
+
+    /// Draw this prompt on the bottom line of `rect` and return the rest of it.
+    ///
+    /// Only the end of the text is shown if it does not fit.
+    pub fn draw<B>(&self, frame: &mut tui::terminal::Frame<B>, rect: Rect) -> Rect
+    where
+        B: tui::backend::Backend,
+    {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .split(rect);
+        let width = (rect.width as usize).saturating_sub(self.label.chars().count() + 1);
+        let skip = self.text.chars().count().saturating_sub(width);
+        let text: String = self.text.chars().skip(skip).collect();
+        let text = [
+            Text::styled(&self.label, Style::default().fg(PROMPT_LABEL_COLOR)),
+            Text::raw(text),
+            Text::styled(" ", Style::default().bg(PROMPT_CURSOR_COLOR)),
+        ];
+        frame.render_widget(Paragraph::new(text.iter()), chunks[1]);
+        chunks[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::buffer_without_style;
+    use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+    #[test]
+    fn test_prompt() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        let mut prompt = Prompt::new("Go to: ", "~/");
+        assert_eq!(
+            prompt.on_event(key(KeyCode::Char('s'))),
+            PromptEvent::Changed
+        );
+        assert_eq!(
+            prompt.on_event(key(KeyCode::Char('r'))),
+            PromptEvent::Changed
+        );
+        assert_eq!(
+            prompt.on_event(key(KeyCode::Char('c'))),
+            PromptEvent::Changed
+        );
+        assert_eq!(prompt.get_text(), "~/src");
+        assert_eq!(
+            prompt.on_event(key(KeyCode::Backspace)),
+            PromptEvent::Changed
+        );
+        assert_eq!(prompt.get_text(), "~/sr");
+        assert_eq!(prompt.on_event(ctrl('w')), PromptEvent::Changed);
+        assert_eq!(prompt.get_text(), "~/");
+        assert_eq!(prompt.on_event(ctrl('u')), PromptEvent::Changed);
+        assert_eq!(prompt.on_event(ctrl('u')), PromptEvent::Ignored);
+        assert_eq!(
+            prompt.on_event(key(KeyCode::Backspace)),
+            PromptEvent::Ignored
+        );
+        assert_eq!(prompt.on_event(key(KeyCode::Tab)), PromptEvent::Completed);
+        assert_eq!(prompt.on_event(key(KeyCode::Enter)), PromptEvent::Submitted);
+        assert_eq!(prompt.on_event(key(KeyCode::Esc)), PromptEvent::Cancelled);
+        assert_eq!(prompt.on_event(key(KeyCode::Up)), PromptEvent::Ignored);
+
+        prompt.set_text("/var/log/nginx");
+        let mut terminal = Terminal::new(TestBackend::new(20, 3)).unwrap();
+        terminal
+            .draw(|mut frame| {
+                let rect = frame.size();
+                let rect = prompt.draw(&mut frame, rect);
+                assert_eq!(rect, Rect::new(0, 0, 20, 2));
+            })
+            .unwrap();
+        assert_eq!(
+            buffer_without_style(terminal.backend().buffer()),
+            Buffer::with_lines(vec![
+                "                    ",
+                "                    ",
+                "Go to: ar/log/nginx ",
+            ])
+        );
+    }
+}
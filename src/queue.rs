@@ -0,0 +1,513 @@
+use crate::file::{FileEntry, LocalFileEntry, RemoteFileEntry, TransferOptions};
+use crate::progress::{Progress, ProgressState};
+use crate::rate_limit::{step_down, step_up, RateLimiter};
+use crate::utils::bitrate_to_string;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
+    widgets::{Block, Borders, List, ListState, Text},
+};
+
+/// The max number of jobs shown in the queue panel at once.
+const QUEUE_MAX_HEIGHT: u16 = 8;
+const QUEUE_HIGHLIGHT_COLOR: Color = Color::LightMagenta;
+
+/// A single upload or download.
+#[derive(Clone)]
+pub enum Transfer {
+    Upload(LocalFileEntry, PathBuf),
+    Download(RemoteFileEntry, PathBuf),
+}
+
+impl Transfer {
+    /// Return the file name of the source of this transfer.
+    pub fn source_filename(&self) -> String {
+        match self {
+            Transfer::Upload(source, _) => source.file_name_lossy().unwrap().to_string(),
+            Transfer::Download(source, _) => source.file_name_lossy().unwrap().to_string(),
+        }
+    }
+
+    /// Return the destination of this transfer.
+    pub fn get_dest(&self) -> &Path {
+        match self {
+            Transfer::Upload(_, dest) => dest,
+            Transfer::Download(_, dest) => dest,
+        }
+    }
+
+    /// Change the destination of this transfer to `dest`.
+    pub fn set_dest(&mut self, dest: PathBuf) {
+        match self {
+            Transfer::Upload(_, old_dest) => *old_dest = dest,
+            Transfer::Download(_, old_dest) => *old_dest = dest,
+        }
+    }
+
+    /// Return a new progress bar for this transfer.
+    fn new_progress(&self) -> Arc<Progress> {
+        let (title, len) = match self {
+            Transfer::Upload(source, _) => (
+                format!("Uploading \"{}\"", self.source_filename()),
+                source.len(),
+            ),
+            Transfer::Download(source, _) => (
+                format!("Downloading \"{}\"", self.source_filename()),
+                source.len(),
+            ),
+        };
+        // The size of a directory is only known once it has been walked.
+        Arc::new(Progress::new(&title, len.unwrap_or(0)))
+    }
+}
+
+/// The state of a job in the queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobState {
+    /// The job is waiting for its turn to start.
+    Pending,
+    /// The job has started, or has been paused or cancelled before it could start.
+    Progress(ProgressState),
+}
+
+struct Job {
+    transfer: Transfer,
+    options: TransferOptions,
+    progress: Arc<Progress>,
+    /// The limit of this job alone, within the limit shared by all jobs.
+    rate_limiter: Arc<RateLimiter>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Job {
+    fn new(transfer: Transfer, options: TransferOptions, rate_limiter: Arc<RateLimiter>) -> Self {
+        Job {
+            progress: transfer.new_progress(),
+            transfer,
+            options,
+            rate_limiter,
+            handle: None,
+        }
+    }
+
+    fn get_state(&self) -> JobState {
+        match (&self.handle, self.progress.get_state()) {
+            (None, ProgressState::Running) => JobState::Pending,
+            (_, state) => JobState::Progress(state),
+        }
+    }
+
+    /// Return `true` if the thread of this job has been started and has not exited yet.
+    ///
+    /// A cancelled job stays active until its thread notices, because it may still be writing
+    /// to the destination.
+    fn is_active(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Returns the text of this job for displaying to the user.
+    fn to_text(&self, width: usize) -> Text<'_> {
+        let state = match self.get_state() {
+            JobState::Pending => "pending".to_string(),
+            JobState::Progress(state) => state.to_string(),
+        };
+        let percent = match self.rate_limiter.get_rate() {
+            0 => format!("{:.0}%", 100.0 * self.progress.get_ratio()),
+            rate => format!(
+                "(limit {}) {:.0}%",
+                bitrate_to_string(8 * rate),
+                100.0 * self.progress.get_ratio()
+            ),
+        };
+        let width = width.saturating_sub(state.len() + percent.len() + 2);
+        Text::raw(format!(
+            "{state} {title:min_width$.max_width$} {percent}",
+            state = state,
+            title = self.progress.get_title(),
+            min_width = width,
+            max_width = width,
+            percent = percent,
+        ))
+    }
+}
+
+/// A queue of transfers that runs at most `max_concurrent` of them at once.
+pub struct TransferQueue {
+    jobs: Vec<Job>,
+    max_concurrent: usize,
+    selected: Option<usize>,
+    /// The limit shared by all jobs.
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl TransferQueue {
+    pub fn new(max_concurrent: usize) -> Self {
+        TransferQueue {
+            jobs: vec![],
+            max_concurrent: max_concurrent.max(1),
+            selected: None,
+            rate_limiter: Arc::new(RateLimiter::new(0)),
+        }
+    }
+
+    /// Add `transfer` to the end of the queue.
+    pub fn push(&mut self, transfer: Transfer, options: TransferOptions) {
+        let rate_limiter = Arc::new(RateLimiter::with_parent(0, Arc::clone(&self.rate_limiter)));
+        self.jobs.push(Job::new(transfer, options, rate_limiter));
+        if self.selected.is_none() {
+            self.selected = Some(0);
+        }
+    }
+
+    /// Start pending jobs in queue order until `max_concurrent` jobs are active.
+    ///
+    /// `spawn` must start a thread that runs the transfer and updates its progress bar.
+    pub fn start_ready<F>(&mut self, mut spawn: F)
+    where
+        F: FnMut(Transfer, TransferOptions, Arc<Progress>, Arc<RateLimiter>) -> JoinHandle<()>,
+    {
+        for job in self.jobs.iter_mut() {
+            if let Some(handle) = &job.handle {
+                // A thread that stopped without updating its progress bar has panicked.
+                if handle.is_finished() && !job.progress.is_finished() {
+                    job.progress.fail();
+                }
+            }
+        }
+
+        let mut num_active = self.jobs.iter().filter(|job| job.is_active()).count();
+        for job in self.jobs.iter_mut() {
+            if num_active >= self.max_concurrent {
+                break;
+            }
+            if job.get_state() == JobState::Pending {
+                let handle = spawn(
+                    job.transfer.clone(),
+                    job.options,
+                    Arc::clone(&job.progress),
+                    Arc::clone(&job.rate_limiter),
+                );
+                job.handle = Some(handle);
+                num_active += 1;
+            }
+        }
+    }
+
+    /// Return `true` if there are jobs that have not stopped yet.
+    pub fn is_busy(&self) -> bool {
+        self.jobs.iter().any(|job| match job.get_state() {
+            JobState::Pending => true,
+            JobState::Progress(state) => !state.is_terminal(),
+        })
+    }
+
+    /// Return the progress bars of all jobs that are currently running or paused.
+    pub fn get_active_progress_bars(&self) -> Vec<&Progress> {
+        self.jobs
+            .iter()
+            .filter(|job| job.is_active())
+            .map(|job| job.progress.as_ref())
+            .collect()
+    }
+
+    /// Set the currently selected job to the next job.
+    pub fn next_selected(&mut self) {
+        self.apply_op_to_selected(|i| i + 1);
+    }
+
+    /// Set the currently selected job to the previous job.
+    pub fn prev_selected(&mut self) {
+        self.apply_op_to_selected(|i| i - 1);
+    }
+
+    /// Apply `f` to the index of the currently selected job.
+    fn apply_op_to_selected<F>(&mut self, f: F)
+    where
+        F: Fn(isize) -> isize,
+    {
+        let n = self.jobs.len() as isize;
+        self.selected = if n == 0 {
+            None
+        } else {
+            let i = self.selected.unwrap_or(0) as isize;
+            Some(f(i).rem_euclid(n) as usize)
+        };
+    }
+
+    fn get_selected_job(&self) -> Option<&Job> {
+        self.selected.map(|i| &self.jobs[i])
+    }
+
+    /// Cancel the selected job. Return `false` if it has already stopped.
+    pub fn cancel_selected(&mut self) -> bool {
+        self.get_selected_job()
+            .map(|job| job.progress.cancel())
+            .unwrap_or(false)
+    }
+
+    /// Pause the selected job. Return `false` if it is not running.
+    pub fn pause_selected(&mut self) -> bool {
+        self.get_selected_job()
+            .map(|job| job.progress.pause())
+            .unwrap_or(false)
+    }
+
+    /// Continue the selected job. Return `false` if it is not paused.
+    pub fn resume_selected(&mut self) -> bool {
+        self.get_selected_job()
+            .map(|job| job.progress.resume())
+            .unwrap_or(false)
+    }
+
+    /// Queue the selected job again if it was cancelled or has failed.
+    ///
+    /// Return `false` if the job cannot be retried, which includes while the thread of a
+    /// cancelled job is still stopping.
+    pub fn retry_selected(&mut self) -> bool {
+        match self.selected {
+            Some(i) if self.jobs[i].is_active() => false,
+            Some(i) => match self.jobs[i].get_state() {
+                JobState::Progress(ProgressState::Cancelled)
+                | JobState::Progress(ProgressState::Failed) => {
+                    let job = &self.jobs[i];
+                    self.jobs[i] = Job::new(
+                        job.transfer.clone(),
+                        job.options,
+                        Arc::clone(&job.rate_limiter),
+                    );
+                    true
+                }
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Return the limit, in bytes per second, shared by all jobs, or zero if there is no limit.
+    pub fn get_limit(&self) -> u64 {
+        self.rate_limiter.get_rate()
+    }
+
+    /// Set the limit, in bytes per second, shared by all jobs, or remove it if `rate` is zero.
+    pub fn set_limit(&self, rate: u64) {
+        self.rate_limiter.set_rate(rate);
+    }
+
+    /// Raise the limit shared by all jobs by one step.
+    pub fn raise_limit(&self) {
+        self.set_limit(step_up(self.get_limit()));
+    }
+
+    /// Lower the limit shared by all jobs by one step.
+    pub fn lower_limit(&self) {
+        let current_rate: u64 = self
+            .get_active_progress_bars()
+            .iter()
+            .map(|progress| progress.get_current_bitrate() / 8)
+            .sum();
+        self.set_limit(step_down(self.get_limit(), current_rate));
+    }
+
+    /// Return the limit, in bytes per second, of the selected job, or zero if it has no limit.
+    pub fn get_selected_limit(&self) -> u64 {
+        self.get_selected_job()
+            .map(|job| job.rate_limiter.get_rate())
+            .unwrap_or(0)
+    }
+
+    /// Raise the limit of the selected job by one step. Return `false` if there is no job.
+    pub fn raise_selected_limit(&self) -> bool {
+        self.get_selected_job()
+            .map(|job| {
+                job.rate_limiter
+                    .set_rate(step_up(job.rate_limiter.get_rate()))
+            })
+            .is_some()
+    }
+
+    /// Lower the limit of the selected job by one step. Return `false` if there is no job.
+    pub fn lower_selected_limit(&self) -> bool {
+        self.get_selected_job()
+            .map(|job| {
+                let current_rate = job.progress.get_current_bitrate() / 8;
+                job.rate_limiter
+                    .set_rate(step_down(job.rate_limiter.get_rate(), current_rate))
+            })
+            .is_some()
+    }
+
+    /// Move the selected pending job one place earlier in the queue.
+    pub fn move_selected_up(&mut self) -> bool {
+        self.move_selected(-1)
+    }
+
+    /// Move the selected pending job one place later in the queue.
+    pub fn move_selected_down(&mut self) -> bool {
+        self.move_selected(1)
+    }
+
+    /// Swap the selected pending job with the job `offset` places away.
+    fn move_selected(&mut self, offset: isize) -> bool {
+        match self.selected {
+            Some(i) if self.jobs[i].get_state() == JobState::Pending => {
+                let j = i as isize + offset;
+                if j >= 0 && (j as usize) < self.jobs.len() {
+                    self.jobs.swap(i, j as usize);
+                    self.selected = Some(j as usize);
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Draw the queue panel at the bottom of `rect` and return the remaining space.
+    pub fn draw<B>(
+        &self,
+        frame: &mut tui::terminal::Frame<B>,
+        rect: tui::layout::Rect,
+    ) -> tui::layout::Rect
+    where
+        B: tui::backend::Backend,
+    {
+        let height = (self.jobs.len() as u16).clamp(1, QUEUE_MAX_HEIGHT) + 2;
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Max(rect.height), Constraint::Length(height)].as_ref())
+            .split(rect);
+        let (rect, queue_rect) = (chunks[0], chunks[1]);
+
+        let width = queue_rect.width.saturating_sub(4) as usize;
+        let items: Vec<_> = self.jobs.iter().map(|job| job.to_text(width)).collect();
+        let title = format!(
+            "Transfers ({} active, max {}{})",
+            self.get_active_progress_bars().len(),
+            self.max_concurrent,
+            match self.get_limit() {
+                0 => String::new(),
+                rate => format!(", limit {}", bitrate_to_string(8 * rate)),
+            }
+        );
+        let list = List::new(items.into_iter())
+            .block(Block::default().title(&title).borders(Borders::ALL))
+            .highlight_style(Style::default().bg(QUEUE_HIGHLIGHT_COLOR))
+            .highlight_symbol(">>");
+        let mut state = ListState::default();
+        state.select(self.selected);
+        frame.render_stateful_widget(list, queue_rect, &mut state);
+
+        rect
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::FileMetadata;
+    use crate::utils::buffer_without_style;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+    fn new_download(name: &str) -> Transfer {
+        Transfer::Download(
+            RemoteFileEntry::File(
+                PathBuf::from(format!("home/{}", name)),
+                100,
+                FileMetadata::default(),
+            ),
+            PathBuf::from(format!("/tmp/{}", name)),
+        )
+    }
+
+    #[test]
+    fn test_transfer_queue() {
+        let mut queue = TransferQueue::new(1);
+        queue.push(new_download("a.txt"), TransferOptions::default());
+        queue.push(new_download("b.txt"), TransferOptions::default());
+        queue.push(new_download("c.txt"), TransferOptions::default());
+
+        // Move "c.txt" in front of "b.txt".
+        queue.next_selected();
+        queue.next_selected();
+        assert!(queue.move_selected_up());
+
+        let mut started = vec![];
+        let can_stop = Arc::new(AtomicBool::new(false));
+        let mut spawn = |transfer: Transfer, _, progress: Arc<Progress>, _| {
+            started.push(transfer.source_filename());
+            let can_stop = Arc::clone(&can_stop);
+            // Keep the job running until it is cancelled and allowed to stop.
+            thread::spawn(move || {
+                while !progress.is_finished() || !can_stop.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            })
+        };
+        queue.start_ready(&mut spawn);
+        assert_eq!(queue.get_active_progress_bars().len(), 1);
+
+        // Only pending jobs can be moved.
+        queue.prev_selected();
+        assert!(!queue.move_selected_down());
+        assert!(queue.pause_selected());
+        assert!(queue.resume_selected());
+        assert!(queue.cancel_selected());
+        assert!(!queue.cancel_selected());
+        // The cancelled job is still active until its thread has stopped.
+        queue.start_ready(&mut spawn);
+        assert_eq!(queue.get_active_progress_bars().len(), 1);
+        assert!(!queue.retry_selected());
+        can_stop.store(true, Ordering::Relaxed);
+        while queue.jobs[0].is_active() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        queue.start_ready(&mut spawn);
+        assert!(queue.retry_selected());
+
+        queue.set_limit(100_000);
+        queue.raise_limit();
+        assert_eq!(queue.get_limit(), 125_000);
+        queue.set_limit(0);
+        assert!(queue.lower_selected_limit());
+        assert_eq!(queue.jobs[0].rate_limiter.get_rate(), 125_000_000);
+        assert!(queue.jobs[0].rate_limiter.is_limited());
+        assert!(queue.raise_selected_limit());
+        assert!(!queue.jobs[0].rate_limiter.is_limited());
+        assert!(queue.is_busy());
+
+        let mut terminal = Terminal::new(TestBackend::new(40, 6)).unwrap();
+        terminal
+            .draw(|mut frame| {
+                let rect = frame.size();
+                queue.draw(&mut frame, rect);
+            })
+            .unwrap();
+
+        for job in queue.jobs.iter() {
+            job.progress.cancel();
+        }
+        assert_eq!(started, vec!["a.txt", "c.txt"]);
+
+        assert_eq!(
+            buffer_without_style(terminal.backend().buffer()),
+            Buffer::with_lines(vec![
+                "                                        ",
+                "┌Transfers (1 active, max 1)───────────┐",
+                "│>>pending Downloading \"a.txt\"       0%│",
+                "│  running Downloading \"c.txt\"       0%│",
+                "│  pending Downloading \"b.txt\"       0%│",
+                "└──────────────────────────────────────┘",
+            ])
+        );
+    }
+}
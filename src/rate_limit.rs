@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The longest time that unused bandwidth is saved up for a burst.
+const MAX_BURST: Duration = Duration::from_millis(500);
+/// The limits, in bits per second, that `step_up()` and `step_down()` move between.
+const LIMIT_STEPS: [u64; 15] = [
+    64_000,
+    128_000,
+    256_000,
+    512_000,
+    1_000_000,
+    2_000_000,
+    4_000_000,
+    8_000_000,
+    16_000_000,
+    32_000_000,
+    64_000_000,
+    128_000_000,
+    256_000_000,
+    512_000_000,
+    1_000_000_000,
+];
+
+struct Bucket {
+    /// The number of bytes that can be sent right away. This is negative when bytes have been
+    /// reserved that have not been paid for yet.
+    tokens: f64,
+    last_update: Instant,
+}
+
+/// A thread-safe token bucket that limits the number of bytes per second.
+///
+/// A limiter can have a parent, e.g., a global limit shared by every transfer, in which case
+/// bytes must fit within both limits.
+pub struct RateLimiter {
+    /// The max number of bytes per second, or zero if there is no limit.
+    rate: AtomicU64,
+    bucket: Mutex<Bucket>,
+    parent: Option<Arc<RateLimiter>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter that allows `rate` bytes per second, or any rate if `rate` is zero.
+    pub fn new(rate: u64) -> Self {
+        RateLimiter {
+            rate: AtomicU64::new(rate),
+            bucket: Mutex::new(Bucket {
+                tokens: 0.0,
+                last_update: Instant::now(),
+            }),
+            parent: None,
+        }
+    }
+
+    /// Create a limiter that allows `rate` bytes per second within the limit of `parent`.
+    pub fn with_parent(rate: u64, parent: Arc<RateLimiter>) -> Self {
+        RateLimiter {
+            parent: Some(parent),
+            ..RateLimiter::new(rate)
+        }
+    }
+
+    /// Return the max number of bytes per second, or zero if there is no limit.
+    pub fn get_rate(&self) -> u64 {
+        self.rate.load(Ordering::Relaxed)
+    }
+
+    /// Set the max number of bytes per second, or remove the limit if `rate` is zero.
+    pub fn set_rate(&self, rate: u64) {
+        self.rate.store(rate, Ordering::Relaxed);
+    }
+
+    /// Return `true` if this limiter or any of its parents has a limit.
+    pub fn is_limited(&self) -> bool {
+        self.get_rate() > 0 || self.parent.as_ref().is_some_and(|p| p.is_limited())
+    }
+
+    /// Take `bytes` from the bucket and return how long to wait before sending them.
+    pub fn reserve(&self, bytes: u64) -> Duration {
+        let wait = self.reserve_at(bytes, Instant::now());
+        match &self.parent {
+            Some(parent) => wait.max(parent.reserve(bytes)),
+            None => wait,
+        }
+    }
+
+    fn reserve_at(&self, bytes: u64, now: Instant) -> Duration {
+        let rate = self.get_rate() as f64;
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.last_update);
+        bucket.last_update = now;
+        if rate == 0.0 {
+            bucket.tokens = 0.0;
+            return Duration::from_secs(0);
+        }
+        let max_tokens = rate * MAX_BURST.as_secs_f64();
+        bucket.tokens = (bucket.tokens + rate * elapsed.as_secs_f64()).min(max_tokens);
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens < 0.0 {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        } else {
+            Duration::from_secs(0)
+        }
+    }
+}
+
+/// Return the next larger limit in bytes per second after `rate`.
+///
+/// There is no limit, i.e., zero, after the largest step.
+pub fn step_up(rate: u64) -> u64 {
+    if rate == 0 {
+        return 0;
+    }
+    LIMIT_STEPS
+        .iter()
+        .map(|bits| bits / 8)
+        .find(|&step| step > rate)
+        .unwrap_or(0)
+}
+
+/// Return the next smaller limit in bytes per second before `rate`.
+///
+/// If there is no limit, the largest step below `current_rate`, the measured number of
+/// bytes per second, is used instead.
+pub fn step_down(rate: u64, current_rate: u64) -> u64 {
+    let (rate, inclusive) = match (rate, current_rate) {
+        (0, 0) => (u64::MAX, true),
+        (0, current_rate) => (current_rate, true),
+        (rate, _) => (rate, false),
+    };
+    LIMIT_STEPS
+        .iter()
+        .rev()
+        .map(|bits| bits / 8)
+        .find(|&step| step < rate || (inclusive && step == rate))
+        .unwrap_or(LIMIT_STEPS[0] / 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(1000);
+        let start = Instant::now();
+        limiter.bucket.lock().unwrap().last_update = start;
+        assert_eq!(limiter.reserve_at(500, start), Duration::from_millis(500));
+        assert_eq!(
+            limiter.reserve_at(500, start + Duration::from_millis(500)),
+            Duration::from_millis(500)
+        );
+        // Unused bandwidth is saved up, but only for `MAX_BURST`.
+        assert_eq!(
+            limiter.reserve_at(500, start + Duration::from_secs(10)),
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            limiter.reserve_at(500, start + Duration::from_secs(10)),
+            Duration::from_millis(500)
+        );
+
+        limiter.set_rate(0);
+        assert!(!limiter.is_limited());
+        assert_eq!(limiter.reserve(1_000_000), Duration::from_secs(0));
+        let child = RateLimiter::with_parent(0, Arc::new(RateLimiter::new(1)));
+        assert!(child.is_limited());
+    }
+
+    #[test]
+    fn test_steps() {
+        assert_eq!(step_up(0), 0);
+        assert_eq!(step_up(8_000), 16_000);
+        assert_eq!(step_up(10_000), 16_000);
+        assert_eq!(step_up(125_000_000), 0);
+        assert_eq!(step_down(16_000, 0), 8_000);
+        assert_eq!(step_down(10_000, 0), 8_000);
+        assert_eq!(step_down(8_000, 0), 8_000);
+        assert_eq!(step_down(0, 0), 125_000_000);
+        assert_eq!(step_down(0, 300_000), 250_000);
+    }
+}
@@ -0,0 +1,92 @@
+use std::io;
+use std::time::Duration;
+
+/// The default number of times a failed transfer is tried again.
+pub const DEFAULT_RETRIES: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How often, and how long after, a transfer that failed with a transient error is tried again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The max number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// The time to wait before the first retry, which doubles after every further attempt.
+    pub initial_backoff: Duration,
+    /// The longest time to wait before any retry.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a policy that tries a transfer again at most `retries` times.
+    pub fn new(retries: u32) -> Self {
+        RetryPolicy {
+            max_attempts: retries.saturating_add(1),
+            initial_backoff: INITIAL_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+        }
+    }
+
+    /// Return how long to wait after the failed attempt number `attempt`, starting at 1,
+    /// or `None` if no attempts are left or `err` is not worth trying again.
+    pub fn get_backoff(&self, attempt: u32, err: &io::Error) -> Option<Duration> {
+        if attempt >= self.max_attempts || !is_transient(err) {
+            return None;
+        }
+        Some(self.get_delay(attempt))
+    }
+
+    /// Return how long to wait after the failed attempt number `attempt`, starting at 1,
+    /// regardless of how many attempts are left.
+    pub fn get_delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+/// Return `true` if `err` might not happen again if the transfer is tried again.
+///
+/// libssh2 reports almost every error, e.g., a timeout or a dropped connection, as
+/// `ErrorKind::Other`, so those are assumed to be transient. Local errors like missing files
+/// or denied permissions, cancelled transfers, and checksum mismatches are not.
+pub fn is_transient(err: &io::Error) -> bool {
+    !matches!(
+        err.kind(),
+        io::ErrorKind::NotFound
+            | io::ErrorKind::PermissionDenied
+            | io::ErrorKind::AlreadyExists
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::InvalidData
+            | io::ErrorKind::Interrupted
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::new(7);
+        let timed_out = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+        let backoffs: Vec<_> = (1..=8)
+            .map(|attempt| policy.get_backoff(attempt, &timed_out))
+            .collect();
+        assert_eq!(
+            backoffs,
+            vec![1, 2, 4, 8, 16, 32, 60]
+                .into_iter()
+                .map(|secs| Some(Duration::from_secs(secs)))
+                .chain(std::iter::once(None))
+                .collect::<Vec<_>>()
+        );
+
+        let not_found = io::Error::new(io::ErrorKind::NotFound, "no such file");
+        assert_eq!(policy.get_backoff(1, &not_found), None);
+        assert_eq!(RetryPolicy::new(0).get_backoff(1, &timed_out), None);
+        assert_eq!(RetryPolicy::new(0).get_delay(3), Duration::from_secs(4));
+    }
+}
@@ -1,26 +1,359 @@
-use crate::connect::create_session;
+use crate::column::Column;
+use crate::completion::{complete, expand_path, split_path, Completion};
+use crate::conflict::{
+    dest_exists, get_unused_path, is_source_newer, ConflictDialog, ConflictPolicy,
+};
+use crate::connect::{get_local_username, Connection};
+use crate::destination::Destination;
 use crate::file::*;
+use crate::filter::Filter;
+use crate::preferences::Preferences;
 use crate::progress::Progress;
+use crate::prompt::{Prompt, PromptEvent};
+use crate::queue::{Transfer, TransferQueue};
+use crate::rate_limit::RateLimiter;
+use crate::retry::{RetryPolicy, DEFAULT_RETRIES};
+use crate::ssh_config::HostConfig;
 use crate::user_message::UserMessage;
+use crate::utils::{bitrate_to_string, duration_to_string, get_remote_home_dir};
 
-use clap;
+use crossbeam_channel::{bounded, Receiver, TryRecvError};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use ssh2;
+use dirs::home_dir;
 use std::error::Error;
-use std::iter::Iterator;
-use std::path::PathBuf;
+use std::fs::read_dir;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    widgets::{Paragraph, Text},
+};
+
+/// The default number of transfers that run at once.
+const DEFAULT_MAX_TRANSFERS: usize = 4;
+/// How often a transfer that is waiting to be tried again checks whether it was cancelled.
+const RETRY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const CONNECTION_LOST_COLOR: Color = Color::Red;
+/// The name of the per-host preference that remembers whether hidden files are shown.
+const SHOW_HIDDEN_FILES: &str = "show_hidden_files";
+/// The number of directories that are listed when Tab has more than one completion.
+const MAX_COMPLETIONS_SHOWN: usize = 20;
+
+/// A new session and its SFTP channel, or why they could not be opened.
+type Reconnection = Result<SessionGeneration, String>;
+
+/// A session with the host and its SFTP channel, numbered so that whoever finds it lost can
+/// tell whether it has already been replaced.
+#[derive(Clone)]
+struct SessionGeneration {
+    id: u64,
+    session: ssh2::Session,
+    sftp: Arc<ssh2::Sftp>,
+}
+
+/// The latest session with the host, shared by the UI and the transfers so that a lost session
+/// is only opened again once, however many of them notice.
+struct SharedSession {
+    latest: Mutex<SessionGeneration>,
+    /// Held while a new session is opened, so that everyone else waits for it.
+    reconnecting: Mutex<()>,
+}
+
+impl SharedSession {
+    fn new(session: ssh2::Session, sftp: Arc<ssh2::Sftp>) -> Self {
+        SharedSession {
+            latest: Mutex::new(SessionGeneration {
+                id: 0,
+                session,
+                sftp,
+            }),
+            reconnecting: Mutex::new(()),
+        }
+    }
+
+    /// Return the latest session, without waiting for one that is being opened.
+    fn get(&self) -> SessionGeneration {
+        self.latest.lock().unwrap().clone()
+    }
+
+    /// Return a session to use instead of the lost generation `lost_id`.
+    ///
+    /// If it has already been replaced, the new session is returned. Otherwise a new one is
+    /// opened with the credentials that were entered when we first connected.
+    fn reconnect(&self, lost_id: u64, connection: &Connection) -> Reconnection {
+        let _reconnecting = self.reconnecting.lock().unwrap();
+        let latest = self.get();
+        if latest.id != lost_id {
+            return Ok(latest);
+        }
+        let session = connection.reconnect().map_err(|err| err.to_string())?;
+        let sftp = session.sftp().map_err(|err| err.to_string())?;
+        let generation = SessionGeneration {
+            id: latest.id + 1,
+            session,
+            sftp: Arc::new(sftp),
+        };
+        *self.latest.lock().unwrap() = generation.clone();
+        Ok(generation)
+    }
+}
+
+/// A line of text that the user is typing, which gets all key presses until it is done.
+enum Input {
+    /// Select the entries that match as the pattern is typed, or the entry at `start` again if
+    /// the search is cancelled.
+    Search {
+        prompt: Prompt,
+        start: Option<PathBuf>,
+    },
+    /// Hide the entries of `pane` that do not match as the pattern is typed, or go back to the
+    /// `previous` filter if it is cancelled.
+    Filter {
+        prompt: Prompt,
+        pane: Pane,
+        previous: Option<Filter>,
+    },
+    /// Change the directory of `pane` to the path that is typed.
+    GoTo { prompt: Prompt, pane: Pane },
+}
+
+impl Input {
+    fn get_prompt(&self) -> &Prompt {
+        match self {
+            Input::Search { prompt, .. } => prompt,
+            Input::Filter { prompt, .. } => prompt,
+            Input::GoTo { prompt, .. } => prompt,
+        }
+    }
+}
+
+/// Whether `Rftp` has a working session with the host.
+enum ConnectionState {
+    Connected {
+        next_keepalive: Instant,
+        /// The result of a keepalive that is being sent in the background, i.e., the number of
+        /// seconds until the next one is due, or `None` if it could not be sent.
+        keepalive: Option<Receiver<Option<u32>>>,
+    },
+    /// The session was lost and a new one is being opened in the background.
+    Reconnecting {
+        attempt: u32,
+        result: Receiver<Reconnection>,
+    },
+    /// The last attempt to open a new session failed, so we wait before trying again.
+    Disconnected { attempt: u32, retry_at: Instant },
+}
+
+impl ConnectionState {
+    fn is_connected(&self) -> bool {
+        matches!(self, ConnectionState::Connected { .. })
+    }
+
+    /// Return a line that tells the user about a lost connection, if there is one.
+    fn get_status(&self) -> Option<String> {
+        match self {
+            ConnectionState::Connected { .. } => None,
+            ConnectionState::Reconnecting { attempt, .. } => Some(format!(
+                "Connection lost. Reconnecting (attempt {})...",
+                attempt
+            )),
+            ConnectionState::Disconnected { retry_at, .. } => Some(format!(
+                "Connection lost. Reconnecting in {}.",
+                duration_to_string(retry_at.saturating_duration_since(Instant::now()))
+            )),
+        }
+    }
+}
 
 pub struct Rftp {
+    connection: Arc<Connection>,
+    shared_session: Arc<SharedSession>,
+    /// The generation of `session` and `sftp` in `shared_session`.
+    session_id: u64,
     session: ssh2::Session,
     sftp: Arc<ssh2::Sftp>,
     files: Arc<Mutex<FileList>>,
     is_alive: bool,
-    progress_bars: Vec<Arc<Progress>>,
+    queue: TransferQueue,
+    is_queue_focused: bool,
+    show_hidden_files: Arc<AtomicBool>,
+    user_message: Arc<UserMessage>,
+    transfer_options: TransferOptions,
+    retry_policy: RetryPolicy,
+    conflict_policy: ConflictPolicy,
+    conflict_dialog: Option<ConflictDialog>,
+    input: Option<Input>,
+    /// The pattern of the last search, for finding the next and previous matches.
+    last_search: Option<Filter>,
+    connection_state: ConnectionState,
+    /// An error from handling a key press, which quits unless the check of the session that
+    /// runs in the background finds that it was lost.
+    failed_key: Option<(Box<dyn Error>, Receiver<bool>)>,
+    /// The host as it was given on the command line, which preferences are saved for.
+    host: String,
+    preferences: Preferences,
+}
+
+/// Everything a transfer thread needs from `Rftp`.
+struct TransferContext {
+    connection: Arc<Connection>,
+    shared_session: Arc<SharedSession>,
+    files: Arc<Mutex<FileList>>,
     show_hidden_files: Arc<AtomicBool>,
     user_message: Arc<UserMessage>,
+    retry_policy: RetryPolicy,
+}
+
+impl TransferContext {
+    /// Spawn a task to run `transfer`, then fetch the files at its destination again.
+    ///
+    /// If the transfer fails with a transient error, it is tried again according to the retry
+    /// policy, continuing from the partial file of the file that failed. If the session has
+    /// been lost, the next attempt uses the one that replaces it.
+    fn spawn(
+        &self,
+        transfer: Transfer,
+        options: TransferOptions,
+        progress: Arc<Progress>,
+        rate_limiter: Arc<RateLimiter>,
+    ) -> JoinHandle<()> {
+        let source_filename = transfer.source_filename();
+        let connection = Arc::clone(&self.connection);
+        let shared_session = Arc::clone(&self.shared_session);
+        let files = Arc::clone(&self.files);
+        let show_hidden_files = Arc::clone(&self.show_hidden_files);
+        let user_message = Arc::clone(&self.user_message);
+        let retry_policy = self.retry_policy;
+
+        thread::spawn(move || {
+            let SessionGeneration {
+                mut id,
+                mut session,
+                mut sftp,
+            } = shared_session.get();
+            let verb = match transfer {
+                Transfer::Upload(..) => "upload",
+                Transfer::Download(..) => "download",
+            };
+            let max_attempts = retry_policy.max_attempts;
+            let mut options = options;
+            let mut attempt = 1;
+            let result = loop {
+                let result = run_transfer(
+                    &transfer,
+                    &session,
+                    &sftp,
+                    &progress,
+                    &rate_limiter,
+                    options,
+                );
+                let (err, backoff) = match result {
+                    Err(err) if !progress.is_cancelled() => {
+                        match retry_policy.get_backoff(attempt, &err) {
+                            Some(backoff) => (err, backoff),
+                            None => break Err(err),
+                        }
+                    }
+                    result => break result,
+                };
+                user_message.report(&format!(
+                    "Error: Unable to {} \"{}\". {} Trying again in {}.",
+                    verb,
+                    source_filename,
+                    err,
+                    duration_to_string(backoff)
+                ));
+                if !sleep_unless_cancelled(&progress, backoff) {
+                    break Err(err);
+                }
+                if sftp.realpath(Path::new(".")).is_err() {
+                    // The session has been lost, so the next attempt needs a new one.
+                    // If we cannot reconnect yet, the next attempt fails and we try again.
+                    if let Ok(generation) = shared_session.reconnect(id, &connection) {
+                        id = generation.id;
+                        session = generation.session;
+                        sftp = generation.sftp;
+                    }
+                }
+                attempt += 1;
+                options.resume_partial = true;
+                progress.restart(attempt, max_attempts);
+            };
+            let result = result.and({
+                let mut files = files.lock().unwrap();
+                let show_hidden_files = show_hidden_files.load(Ordering::Relaxed);
+                match transfer {
+                    Transfer::Upload(..) => files.fetch_remote_files(&sftp, show_hidden_files),
+                    Transfer::Download(..) => files.fetch_local_files(show_hidden_files),
+                }
+            });
+            if progress.is_cancelled() {
+                user_message.report(&format!("Cancelled {}ing \"{}\".", verb, source_filename));
+            } else if let Err(err) = result {
+                progress.fail();
+                user_message.report(&format!(
+                    "Error: Unable to {} \"{}\"{}. {}",
+                    verb,
+                    source_filename,
+                    if attempt > 1 {
+                        format!(" after {} attempts", attempt)
+                    } else {
+                        String::new()
+                    },
+                    err
+                ));
+            } else {
+                progress.finish();
+                user_message.report(&format!("Finished {}ing \"{}\".", verb, source_filename));
+            }
+        })
+    }
+}
+
+/// Run a single attempt of `transfer`.
+fn run_transfer(
+    transfer: &Transfer,
+    session: &ssh2::Session,
+    sftp: &ssh2::Sftp,
+    progress: &Progress,
+    rate_limiter: &RateLimiter,
+    options: TransferOptions,
+) -> io::Result<()> {
+    match transfer.clone() {
+        Transfer::Upload(source, dest) => {
+            if source.is_dir() {
+                upload_directory(source, dest, session, sftp, progress, rate_limiter, options)
+            } else {
+                upload(source, dest, session, sftp, progress, rate_limiter, options)
+            }
+        }
+        Transfer::Download(source, dest) => {
+            if source.is_dir() {
+                download_directory(source, dest, session, sftp, progress, rate_limiter, options)
+            } else {
+                download(source, dest, session, sftp, progress, rate_limiter, options)
+            }
+        }
+    }
+}
+
+/// Sleep for `duration`, or while `progress` is paused.
+///
+/// Return `false` if `progress` is cancelled in the meantime.
+fn sleep_unless_cancelled(progress: &Progress, duration: Duration) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < duration || progress.is_paused() {
+        if progress.is_cancelled() {
+            return false;
+        }
+        thread::sleep(RETRY_POLL_INTERVAL);
+    }
+    !progress.is_cancelled()
 }
 
 impl Rftp {
@@ -30,57 +363,355 @@ impl Rftp {
                 (version: clap::crate_version!())
                 (author: clap::crate_authors!())
                 (about: clap::crate_description!())
-                (@arg destination: +required)
+                (@arg destination: +required
+                    "Host to connect to, e.g., user@host, user@host:/start/dir, [::1]:2222, or sftp://user@host:port/path")
                 (@arg port: -p --port +takes_value)
                 (@arg username: -u --user +takes_value)
+                (@arg identity_file: -i +takes_value +multiple number_of_values(1)
+                    "Private key to authenticate with, tried before any from ~/.ssh/config")
+                (@arg jump_hosts: -J +takes_value
+                    "Connect through comma-separated jump hosts, e.g., user@bastion:2222")
+                (@arg proxy_command: --("proxy-command") +takes_value conflicts_with[jump_hosts]
+                    "Connect through the standard input and output of a command, e.g., \"nc -X 5 -x proxy:1080 %h %p\"")
+                (@arg strict_host_key_checking: --("strict-host-key-checking") +takes_value
+                    possible_values(&["ask", "yes", "accept-new", "no"])
+                    "What to do with host keys that are not known")
                 (@arg verbose: -v --verbose)
+                (@arg all: -a --all "Show hidden files")
+                (@arg no_all: --("no-all") conflicts_with[all]
+                    "Hide hidden files, even if they were shown last time")
+                (@arg resume: --resume "Resume interrupted transfers instead of starting over")
+                (@arg requests: -R --requests +takes_value "Size of each read and write in 32 KiB SFTP requests")
+                (@arg verify: --verify "Compare checksums after each file is transferred")
+                (@arg preserve: --preserve "Preserve permissions and access/modification times")
+                (@arg limit: -l --limit +takes_value "Limit the bandwidth of all transfers in Kbit/s")
+                (@arg retries: --retries +takes_value "Number of times to try a failed transfer again")
+                (@arg max_transfers: --("max-transfers") +takes_value "Number of transfers to run at once")
+                (@arg on_conflict: --("on-conflict") +takes_value
+                    possible_values(&["ask", "overwrite", "skip", "rename", "resume", "newer"])
+                    "What to do when the destination already exists")
+                (@arg columns: --columns +takes_value
+                    "Columns to show after the file names: size, modified, permissions, owner, link")
         )
         .get_matches();
 
-        let destination = matches.value_of("destination").unwrap();
+        let destination = matches
+            .value_of("destination")
+            .unwrap()
+            .parse::<Destination>()?;
+        // Settings from the command line override the ones from `~/.ssh/config`, and flags
+        // override the destination.
+        let mut config = HostConfig::load(&destination.host)
+            .map_err(|err| format!("unable to read ssh config: {}", err))?;
+        if let Some(port) = matches.value_of("port") {
+            config.port = Some(
+                port.parse::<u16>()
+                    .map_err(|_| "unable to parse port number")?,
+            );
+        } else if destination.port.is_some() {
+            config.port = destination.port;
+        }
+        if let Some(identity_files) = matches.values_of("identity_file") {
+            let identity_files = identity_files.map(PathBuf::from);
+            config.identity_files = identity_files
+                .chain(config.identity_files.drain(..))
+                .collect();
+        }
+        if let Some(mode) = matches.value_of("strict_host_key_checking") {
+            config.strict_host_key_checking = Some(mode.parse()?);
+        }
+        if let Some(jump_hosts) = matches.value_of("jump_hosts") {
+            config.proxy_jump = Some(jump_hosts.to_string());
+            config.proxy_command = None;
+        }
+        if let Some(proxy_command) = matches.value_of("proxy_command") {
+            config.proxy_command = Some(proxy_command.to_string());
+            config.proxy_jump = None;
+        }
         let username = {
             if let Some(username) = matches.value_of("username") {
                 username.to_string()
-            } else if cfg!(unix) {
-                std::env::var("USER")?
-            } else if cfg!(windows) {
-                std::env::var("USERNAME")?
+            } else if let Some(username) = &destination.user {
+                username.clone()
+            } else if let Some(username) = &config.user {
+                username.clone()
             } else {
-                unimplemented!()
+                get_local_username()?
             }
         };
-        let port = matches.value_of("port");
         let verbose = matches.is_present("verbose");
-        let session = create_session(destination, &username, port, verbose)?;
-        let sftp = session.sftp()?;
+        let requests = matches
+            .value_of("requests")
+            .map(|requests| requests.parse::<usize>())
+            .transpose()
+            .map_err(|_| "unable to parse number of requests")?
+            .unwrap_or(DEFAULT_REQUESTS);
+        let max_transfers = matches
+            .value_of("max_transfers")
+            .map(|max_transfers| max_transfers.parse::<usize>())
+            .transpose()
+            .map_err(|_| "unable to parse max number of transfers")?
+            .unwrap_or(DEFAULT_MAX_TRANSFERS);
+        let retries = matches
+            .value_of("retries")
+            .map(|retries| retries.parse::<u32>())
+            .transpose()
+            .map_err(|_| "unable to parse number of retries")?
+            .unwrap_or(DEFAULT_RETRIES);
+        let limit = matches
+            .value_of("limit")
+            .map(|limit| limit.parse::<u64>())
+            .transpose()
+            .map_err(|_| "unable to parse bandwidth limit")?
+            .unwrap_or(0);
+        let transfer_options = TransferOptions {
+            resume: matches.is_present("resume"),
+            resume_partial: false,
+            requests,
+            verify: matches.is_present("verify"),
+            preserve: matches.is_present("preserve"),
+        };
+        let conflict_policy = matches
+            .value_of("on_conflict")
+            .map(|policy| policy.parse::<ConflictPolicy>())
+            .transpose()?
+            .unwrap_or(ConflictPolicy::Ask);
+        let columns = matches
+            .value_of("columns")
+            .map(Column::parse_list)
+            .transpose()?
+            .unwrap_or_else(|| vec![Column::Size]);
+        let connection = Arc::new(Connection::new(config, &username, verbose)?);
+        let session = connection.connect()?;
+        let sftp = Arc::new(session.sftp()?);
+        let shared_session = Arc::new(SharedSession::new(session.clone(), Arc::clone(&sftp)));
 
-        let show_hidden_files = false;
+        let preferences = Preferences::load();
+        let show_hidden_files = if matches.is_present("all") {
+            true
+        } else if matches.is_present("no_all") {
+            false
+        } else {
+            preferences.get(&destination.host, SHOW_HIDDEN_FILES) == Some("true")
+        };
 
-        let files = Arc::new(Mutex::new(FileList::new(
+        let mut files = FileList::new(
             &session,
             &sftp,
+            destination.path.as_deref(),
             show_hidden_files,
-        )?));
+        )?;
+        files.set_columns(columns);
+        let files = Arc::new(Mutex::new(files));
+
+        let queue = TransferQueue::new(max_transfers);
+        // Kbit/s to bytes per second.
+        queue.set_limit(limit * 1000 / 8);
 
         Ok(Rftp {
+            connection,
+            shared_session,
+            session_id: 0,
             session,
-            sftp: Arc::new(sftp),
+            sftp,
             files,
             is_alive: true,
-            progress_bars: vec![],
+            queue,
+            is_queue_focused: false,
             show_hidden_files: Arc::new(AtomicBool::new(show_hidden_files)),
             user_message: Arc::new(UserMessage::new()),
+            transfer_options,
+            retry_policy: RetryPolicy::new(retries),
+            conflict_policy,
+            conflict_dialog: None,
+            input: None,
+            last_search: None,
+            connection_state: ConnectionState::Connected {
+                next_keepalive: Instant::now(),
+                keepalive: None,
+            },
+            failed_key: None,
+            host: destination.host,
+            preferences,
         })
     }
 
     /// Work that is done on every "tick".
     pub fn tick(&mut self) -> Result<(), Box<dyn Error>> {
-        self.progress_bars.retain(|p| !p.is_finished());
+        if let Some((err, is_alive)) = self.failed_key.take() {
+            match is_alive.try_recv() {
+                Ok(true) => return Err(err),
+                Ok(false) | Err(TryRecvError::Disconnected) => {
+                    self.user_message.report(&format!("Error: {}", err));
+                    if self.connection_state.is_connected() {
+                        self.on_connection_lost();
+                    }
+                }
+                Err(TryRecvError::Empty) => self.failed_key = Some((err, is_alive)),
+            }
+        }
+        self.check_connection();
+        // Transfers wait in the queue until we are connected again.
+        if self.connection_state.is_connected() {
+            let context = self.get_transfer_context();
+            self.queue
+                .start_ready(|transfer, options, progress, rate_limiter| {
+                    context.spawn(transfer, options, progress, rate_limiter)
+                });
+        }
         Ok(())
     }
 
+    /// Send a keepalive message when one is due, and open a new session in the background
+    /// once the current one is lost.
+    ///
+    /// Keepalives are sent from another thread, since a lost session can block until it
+    /// times out.
+    fn check_connection(&mut self) {
+        let now = Instant::now();
+        match &self.connection_state {
+            ConnectionState::Connected {
+                next_keepalive,
+                keepalive: None,
+            } if now >= *next_keepalive => {
+                let (sender, receiver) = bounded(1);
+                let session = self.session.clone();
+                thread::spawn(move || sender.send(session.keepalive_send().ok()).ok());
+                self.connection_state = ConnectionState::Connected {
+                    next_keepalive: *next_keepalive,
+                    keepalive: Some(receiver),
+                };
+            }
+            ConnectionState::Connected {
+                keepalive: Some(keepalive),
+                ..
+            } => match keepalive.try_recv() {
+                Ok(Some(seconds)) => {
+                    self.connection_state = ConnectionState::Connected {
+                        next_keepalive: now + Duration::from_secs(seconds.max(1).into()),
+                        keepalive: None,
+                    };
+                }
+                Ok(None) | Err(TryRecvError::Disconnected) => self.on_connection_lost(),
+                Err(TryRecvError::Empty) => {}
+            },
+            ConnectionState::Connected { .. } => {}
+            ConnectionState::Reconnecting { attempt, result } => match result.try_recv() {
+                Ok(Ok(generation)) => self.on_reconnected(generation),
+                Ok(Err(err)) => {
+                    let attempt = *attempt;
+                    self.user_message
+                        .report(&format!("Error: Unable to reconnect. {}", err));
+                    self.connection_state = ConnectionState::Disconnected {
+                        attempt,
+                        retry_at: now + self.retry_policy.get_delay(attempt),
+                    };
+                }
+                Err(_) => {}
+            },
+            ConnectionState::Disconnected { attempt, retry_at } if now >= *retry_at => {
+                let attempt = *attempt;
+                self.start_reconnecting(attempt + 1);
+            }
+            ConnectionState::Disconnected { .. } => {}
+        }
+    }
+
+    fn on_connection_lost(&mut self) {
+        self.user_message
+            .report("Error: The connection to the host was lost.");
+        self.start_reconnecting(1);
+    }
+
+    /// Open a new session with the credentials that were entered when we first connected.
+    fn start_reconnecting(&mut self, attempt: u32) {
+        let (sender, receiver) = bounded(1);
+        let connection = Arc::clone(&self.connection);
+        let shared_session = Arc::clone(&self.shared_session);
+        let session_id = self.session_id;
+        thread::spawn(move || {
+            sender
+                .send(shared_session.reconnect(session_id, &connection))
+                .ok();
+        });
+        self.connection_state = ConnectionState::Reconnecting {
+            attempt,
+            result: receiver,
+        };
+    }
+
+    /// Use the new session and go back to the remote directory we were in.
+    fn on_reconnected(&mut self, generation: SessionGeneration) {
+        self.session_id = generation.id;
+        self.session = generation.session;
+        self.sftp = generation.sftp;
+        self.connection_state = ConnectionState::Connected {
+            next_keepalive: Instant::now(),
+            keepalive: None,
+        };
+        let mut files = self.files.lock().unwrap();
+        let remote_path = files.get_remote_working_path().to_path_buf();
+        let result = files.set_remote_working_path(
+            &remote_path,
+            &self.sftp,
+            self.show_hidden_files.load(Ordering::Relaxed),
+        );
+        drop(files);
+        match result {
+            Ok(()) => self.user_message.report("Reconnected to the host."),
+            Err(err) => self.user_message.report(&format!(
+                "Reconnected to the host, but unable to open {:?}. {}",
+                remote_path, err
+            )),
+        }
+    }
+
     /// Work that is done on every key press.
+    ///
+    /// If handling the key fails because the session was lost, the error is reported and a
+    /// new session is opened instead of quitting. Whether it was lost is checked in the
+    /// background, so the app only quits on a later tick.
     pub fn on_event(&mut self, key: KeyEvent) -> Result<(), Box<dyn Error>> {
+        match self.on_key(key) {
+            Err(err) if !self.connection_state.is_connected() || self.failed_key.is_some() => {
+                self.user_message.report(&format!("Error: {}", err));
+                Ok(())
+            }
+            Err(err) => {
+                self.failed_key = Some((err, self.spawn_session_check()));
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Check whether the host still answers SFTP requests on another thread, since a lost
+    /// session can block until it times out.
+    fn spawn_session_check(&self) -> Receiver<bool> {
+        let (sender, receiver) = bounded(1);
+        let sftp = Arc::clone(&self.sftp);
+        thread::spawn(move || sender.send(sftp.realpath(Path::new(".")).is_ok()).ok());
+        receiver
+    }
+
+    fn on_key(&mut self, key: KeyEvent) -> Result<(), Box<dyn Error>> {
+        if let Some(dialog) = &mut self.conflict_dialog {
+            if let Some((transfer, policy, apply_to_all)) = dialog.on_event(key) {
+                self.conflict_dialog = None;
+                if apply_to_all {
+                    self.conflict_policy = policy;
+                }
+                self.resolve_conflict(transfer, policy);
+            }
+            return Ok(());
+        }
+        if let Some(input) = self.input.take() {
+            self.on_input_event(input, key);
+            return Ok(());
+        }
+        if self.is_queue_focused && self.on_queue_event(key) {
+            return Ok(());
+        }
         match key {
             KeyEvent {
                 code: KeyCode::Char('Q'),
@@ -92,7 +723,7 @@ impl Rftp {
                 code: KeyCode::Char('q'),
                 modifiers: KeyModifiers::NONE,
             } => {
-                if self.progress_bars.is_empty() {
+                if !self.queue.is_busy() {
                     self.is_alive = false;
                 } else {
                     self.user_message.report(
@@ -120,6 +751,11 @@ impl Rftp {
                             ));
                         }
                     }
+                    SelectedFileEntry::Remote(_) if !self.connection_state.is_connected() => {
+                        drop(files);
+                        self.user_message
+                            .report("Error: Not connected to the host, please wait.");
+                    }
                     SelectedFileEntry::Remote(entry) => {
                         if entry.is_dir() {
                             files.set_remote_working_path(
@@ -148,33 +784,29 @@ impl Rftp {
                 let files = self.files.lock().unwrap();
                 match files.get_selected_entry() {
                     SelectedFileEntry::Local(source) => {
-                        if source.is_file() {
+                        if !source.is_parent() {
                             let dest = files
                                 .get_remote_working_path()
                                 .join(source.path().file_name().unwrap());
                             drop(files);
-                            self.spawn_upload(source, dest);
+                            self.enqueue(Transfer::Upload(source, dest));
                         } else {
                             drop(files);
-                            self.user_message.report(&format!(
-                                "Error: Cannot upload \"{}\" because it is a directory!",
-                                source.file_name_lossy().unwrap()
-                            ));
+                            self.user_message
+                                .report("Error: Cannot upload the parent directory!");
                         }
                     }
                     SelectedFileEntry::Remote(source) => {
-                        if source.is_file() {
+                        if !source.is_parent() {
                             let dest = files
                                 .get_local_working_path()
                                 .join(source.path().file_name().unwrap());
                             drop(files);
-                            self.spawn_download(source, dest);
+                            self.enqueue(Transfer::Download(source, dest));
                         } else {
                             drop(files);
-                            self.user_message.report(&format!(
-                                "Error: Cannot download \"{}\" because it is a directory!",
-                                source.file_name_lossy().unwrap()
-                            ));
+                            self.user_message
+                                .report("Error: Cannot download the parent directory!");
                         }
                     }
                     SelectedFileEntry::None => {
@@ -183,6 +815,26 @@ impl Rftp {
                     }
                 }
             }
+            KeyEvent {
+                code: KeyCode::Tab,
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.is_queue_focused = !self.is_queue_focused;
+            }
+            KeyEvent {
+                code: KeyCode::Char(']'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.queue.raise_limit();
+                self.report_limit("all transfers", self.queue.get_limit());
+            }
+            KeyEvent {
+                code: KeyCode::Char('['),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.queue.lower_limit();
+                self.report_limit("all transfers", self.queue.get_limit());
+            }
             KeyEvent {
                 code: KeyCode::Char('j'),
                 modifiers: KeyModifiers::NONE,
@@ -221,75 +873,505 @@ impl Rftp {
             } => {
                 self.files.lock().unwrap().toggle_selected();
             }
+            KeyEvent {
+                code: KeyCode::Char('.'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.toggle_hidden_files()?;
+            }
+            KeyEvent {
+                code: KeyCode::Char('/'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                let start = self.files.lock().unwrap().get_selected_path();
+                self.input = Some(Input::Search {
+                    prompt: Prompt::new("/", ""),
+                    start,
+                });
+            }
+            KeyEvent {
+                code: KeyCode::Char('n'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.select_next_match(false);
+            }
+            KeyEvent {
+                code: KeyCode::Char('N'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.select_next_match(true);
+            }
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                let files = self.files.lock().unwrap();
+                if let Some(pane) = files.get_selected_pane() {
+                    let previous = files.get_filter(pane).cloned();
+                    drop(files);
+                    let text = previous.as_ref().map(Filter::to_string).unwrap_or_default();
+                    self.input = Some(Input::Filter {
+                        prompt: Prompt::new("Filter: ", &text),
+                        pane,
+                        previous,
+                    });
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Char('g'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                let pane = self
+                    .files
+                    .lock()
+                    .unwrap()
+                    .get_selected_pane()
+                    .unwrap_or(Pane::Remote);
+                let label = match pane {
+                    Pane::Local => "Go to local: ",
+                    Pane::Remote => "Go to remote: ",
+                };
+                self.input = Some(Input::GoTo {
+                    prompt: Prompt::new(label, ""),
+                    pane,
+                });
+            }
+            KeyEvent {
+                code: KeyCode::Char('s'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.change_sort(|sort| Sort {
+                    mode: sort.mode.next(),
+                    ..sort
+                });
+            }
+            KeyEvent {
+                code: KeyCode::Char('S'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.change_sort(|sort| Sort {
+                    descending: !sort.descending,
+                    ..sort
+                });
+            }
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::NONE,
+            } => {
+                self.change_sort(|sort| Sort {
+                    directories_first: !sort.directories_first,
+                    ..sort
+                });
+            }
             _ => {}
         };
         Ok(())
     }
 
-    /// Spawn a task to upload `source` to `dest`, then fetch the remote files again.
-    fn spawn_upload(&mut self, source: LocalFileEntry, dest: PathBuf) {
-        let source_filename = source.file_name_lossy().unwrap().to_string();
-        let progress = {
-            let title = format!("Uploading \"{}\"", source_filename);
-            let len = source.len().unwrap();
-            Arc::new(Progress::new(&title, len))
+    /// Handle a key press while the user is typing `input`.
+    fn on_input_event(&mut self, mut input: Input, key: KeyEvent) {
+        if let Input::GoTo { prompt, pane } = &mut input {
+            match prompt.on_event(key) {
+                PromptEvent::Submitted => {
+                    self.go_to(prompt.get_text(), *pane);
+                    return;
+                }
+                PromptEvent::Cancelled => return,
+                PromptEvent::Completed => self.complete_path(prompt, *pane),
+                PromptEvent::Changed | PromptEvent::Ignored => {}
+            }
+            self.input = Some(input);
+            return;
+        }
+        let mut files = self.files.lock().unwrap();
+        let is_done = match &mut input {
+            Input::Search { prompt, start } => match prompt.on_event(key) {
+                PromptEvent::Changed => {
+                    // Search again from where we started, so that typing more of a name does
+                    // not skip past it.
+                    files.reselect(start.clone());
+                    if !prompt.get_text().is_empty() {
+                        files.select_match(&Filter::new(prompt.get_text()), true, false);
+                    }
+                    false
+                }
+                PromptEvent::Submitted => {
+                    if !prompt.get_text().is_empty() {
+                        self.last_search = Some(Filter::new(prompt.get_text()));
+                    }
+                    true
+                }
+                PromptEvent::Cancelled => {
+                    files.reselect(start.take());
+                    true
+                }
+                PromptEvent::Completed | PromptEvent::Ignored => false,
+            },
+            Input::Filter {
+                prompt,
+                pane,
+                previous,
+            } => match prompt.on_event(key) {
+                PromptEvent::Changed => {
+                    files.set_filter(*pane, get_filter(prompt.get_text()));
+                    false
+                }
+                PromptEvent::Submitted => true,
+                PromptEvent::Cancelled => {
+                    files.set_filter(*pane, previous.take());
+                    true
+                }
+                PromptEvent::Completed | PromptEvent::Ignored => false,
+            },
+            Input::GoTo { .. } => unreachable!(),
         };
-        self.progress_bars.push(Arc::clone(&progress));
-
-        let sftp = Arc::clone(&self.sftp);
-        let user_message = Arc::clone(&self.user_message);
-        let show_hidden_files = Arc::clone(&self.show_hidden_files);
-        let files = Arc::clone(&self.files);
+        drop(files);
+        if !is_done {
+            self.input = Some(input);
+        }
+    }
 
-        thread::spawn(move || {
-            let result = upload(source, dest, &sftp, &progress).and({
-                let mut files = files.lock().unwrap();
-                files.fetch_remote_files(&sftp, show_hidden_files.load(Ordering::Relaxed))
-            });
-            if let Err(err) = result {
-                progress.finish();
-                user_message.report(&format!(
-                    "Error: Unable to upload \"{}\". {}",
-                    source_filename,
-                    err.to_string()
-                ));
-            } else {
-                user_message.report(&format!("Finished uploading \"{}\".", source_filename));
+    /// Change the directory of `pane` to the path `text`, or tell the user why it cannot be
+    /// changed and stay in the current one.
+    fn go_to(&mut self, text: &str, pane: Pane) {
+        if text.is_empty() {
+            return;
+        }
+        let show_hidden_files = self.show_hidden_files.load(Ordering::Relaxed);
+        let result = self.expand_path(text, pane).and_then(|path| {
+            let mut files = self.files.lock().unwrap();
+            match pane {
+                Pane::Local => files.set_local_working_path(&path, show_hidden_files),
+                Pane::Remote => files.set_remote_working_path(&path, &self.sftp, show_hidden_files),
             }
+            .map_err(|err| format!("Cannot go to {:?}. {}", path, err))
         });
+        if let Err(err) = result {
+            self.user_message.report(&format!("Error: {}", err));
+        }
     }
 
-    /// Spawn a task to download `source` to `dest`, then fetch the local files again.
-    fn spawn_download(&mut self, source: RemoteFileEntry, dest: PathBuf) {
-        let source_filename = source.file_name_lossy().unwrap().to_string();
-        let progress = {
-            let title = format!("Downloading \"{}\"", source_filename);
-            let len = source.len().unwrap();
-            Arc::new(Progress::new(&title, len))
+    /// Complete the name of the directory at the end of the path that is typed in `prompt`.
+    ///
+    /// If more than one directory starts with the name, they are listed for the user.
+    fn complete_path(&self, prompt: &mut Prompt, pane: Pane) {
+        let (dir, _) = split_path(prompt.get_text());
+        let names = self
+            .expand_path(dir, pane)
+            .and_then(|dir| self.get_directory_names(&dir, pane));
+        match names.map(|names| complete(prompt.get_text(), &names)) {
+            Ok(Completion::Text(text)) => prompt.set_text(&text),
+            Ok(Completion::Ambiguous(names)) => {
+                let mut message = names[..names.len().min(MAX_COMPLETIONS_SHOWN)].join("  ");
+                if names.len() > MAX_COMPLETIONS_SHOWN {
+                    message.push_str("  ...");
+                }
+                self.user_message.report(&message);
+            }
+            Ok(Completion::None) => {}
+            Err(err) => self.user_message.report(&format!("Error: {}", err)),
+        }
+    }
+
+    /// Return the path that `text` refers to in `pane`, where `~` is the home directory and a
+    /// relative path is relative to the directory of the pane.
+    fn expand_path(&self, text: &str, pane: Pane) -> Result<PathBuf, String> {
+        let files = self.files.lock().unwrap();
+        let path = match pane {
+            Pane::Local => expand_path(text, files.get_local_working_path(), home_dir().as_deref()),
+            Pane::Remote if !self.connection_state.is_connected() => {
+                return Err(String::from("Not connected to the host, please wait."));
+            }
+            Pane::Remote => {
+                let home_dir = if text.starts_with('~') {
+                    get_remote_home_dir(&self.session, &self.sftp).ok()
+                } else {
+                    None
+                };
+                expand_path(text, files.get_remote_working_path(), home_dir.as_deref())
+            }
         };
-        self.progress_bars.push(Arc::clone(&progress));
+        path.ok_or_else(|| String::from("Unable to find the home directory."))
+    }
 
-        let sftp = Arc::clone(&self.sftp);
-        let user_message = Arc::clone(&self.user_message);
-        let show_hidden_files = Arc::clone(&self.show_hidden_files);
-        let files = Arc::clone(&self.files);
+    /// Return the names of the directories in `dir` in `pane`, including symbolic links to
+    /// directories.
+    fn get_directory_names(&self, dir: &Path, pane: Pane) -> Result<Vec<String>, String> {
+        let names = match pane {
+            Pane::Local => read_dir(dir).map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_dir())
+                    .filter_map(|path| Some(path.file_name()?.to_string_lossy().into_owned()))
+                    .collect()
+            }),
+            Pane::Remote => self
+                .sftp
+                .readdir(dir)
+                .map(|entries| {
+                    entries
+                        .into_iter()
+                        .filter(|(path, stat)| {
+                            stat.is_dir()
+                                || (stat.file_type().is_symlink()
+                                    && self.sftp.stat(path).is_ok_and(|stat| stat.is_dir()))
+                        })
+                        .filter_map(|(path, _)| {
+                            Some(path.file_name()?.to_string_lossy().into_owned())
+                        })
+                        .collect()
+                })
+                .map_err(io::Error::from),
+        };
+        names.map_err(|err| format!("Cannot read {:?}. {}", dir, err))
+    }
 
-        thread::spawn(move || {
-            let result = download(source, dest, &sftp, &progress).and({
-                let mut files = files.lock().unwrap();
-                files.fetch_local_files(show_hidden_files.load(Ordering::Relaxed))
-            });
-            if let Err(err) = result {
-                progress.finish();
-                user_message.report(&format!(
-                    "Error: Unable to download \"{}\". {}",
-                    source_filename,
-                    err.to_string()
-                ));
+    /// Select the next entry that matches the last search, or the previous one if `reverse` is
+    /// set.
+    fn select_next_match(&mut self, reverse: bool) {
+        let error = match &self.last_search {
+            Some(search) => {
+                if self
+                    .files
+                    .lock()
+                    .unwrap()
+                    .select_match(search, false, reverse)
+                {
+                    None
+                } else {
+                    Some(format!("Error: No entries match \"{}\".", search))
+                }
+            }
+            None => Some(String::from("Error: Nothing has been searched for.")),
+        };
+        if let Some(error) = error {
+            self.user_message.report(&error);
+        }
+    }
+
+    /// Handle a key press while the transfer queue has focus.
+    ///
+    /// Return `false` if the key has no meaning in the transfer queue.
+    fn on_queue_event(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers != KeyModifiers::NONE {
+            return false;
+        }
+        let error = match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.queue.next_selected();
+                None
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.queue.prev_selected();
+                None
+            }
+            KeyCode::Char('c') if !self.queue.cancel_selected() => {
+                Some("Error: The selected transfer has already stopped.")
+            }
+            KeyCode::Char('p') if !self.queue.pause_selected() => {
+                Some("Error: The selected transfer is not running.")
+            }
+            KeyCode::Char('r') if !self.queue.resume_selected() => {
+                Some("Error: The selected transfer is not paused.")
+            }
+            KeyCode::Char('R') if !self.queue.retry_selected() => {
+                Some("Error: Only cancelled or failed transfers that have stopped can be retried.")
+            }
+            KeyCode::Char('K') if !self.queue.move_selected_up() => {
+                Some("Error: Only pending transfers can be moved.")
+            }
+            KeyCode::Char('J') if !self.queue.move_selected_down() => {
+                Some("Error: Only pending transfers can be moved.")
+            }
+            KeyCode::Char('>') | KeyCode::Char('<') => {
+                let is_selected = if key.code == KeyCode::Char('>') {
+                    self.queue.raise_selected_limit()
+                } else {
+                    self.queue.lower_selected_limit()
+                };
+                if is_selected {
+                    self.report_limit("the selected transfer", self.queue.get_selected_limit());
+                    None
+                } else {
+                    Some("Error: No transfer selected.")
+                }
+            }
+            KeyCode::Char('c')
+            | KeyCode::Char('p')
+            | KeyCode::Char('r')
+            | KeyCode::Char('R')
+            | KeyCode::Char('K')
+            | KeyCode::Char('J') => None,
+            _ => return false,
+        };
+        if let Some(error) = error {
+            self.user_message.report(error);
+        }
+        true
+    }
+
+    /// Show hidden files if they are hidden or hide them if they are shown, and remember the
+    /// choice for this host.
+    fn toggle_hidden_files(&mut self) -> io::Result<()> {
+        let show_hidden_files = !self.show_hidden_files.load(Ordering::Relaxed);
+        self.show_hidden_files
+            .store(show_hidden_files, Ordering::Relaxed);
+        // The remote files are fetched again once we are connected.
+        let sftp = if self.connection_state.is_connected() {
+            Some(self.sftp.as_ref())
+        } else {
+            None
+        };
+        self.files
+            .lock()
+            .unwrap()
+            .refresh(sftp, show_hidden_files)?;
+
+        self.preferences.set(
+            &self.host,
+            SHOW_HIDDEN_FILES,
+            if show_hidden_files { "true" } else { "false" },
+        );
+        if let Err(err) = self.preferences.save() {
+            self.user_message
+                .report(&format!("Error: Unable to save preferences. {}", err));
+        } else if show_hidden_files {
+            self.user_message.report("Showing hidden files.");
+        } else {
+            self.user_message.report("Hiding hidden files.");
+        }
+        Ok(())
+    }
+
+    /// Sort the file lists in the order that `f` returns for the current one.
+    fn change_sort<F: FnOnce(Sort) -> Sort>(&mut self, f: F) {
+        let mut files = self.files.lock().unwrap();
+        let sort = f(files.get_sort());
+        files.set_sort(sort);
+        drop(files);
+        self.user_message.report(&format!(
+            "Sorting by {}, {}{}.",
+            sort.mode,
+            if sort.descending {
+                "descending"
             } else {
-                user_message.report(&format!("Finished downloading \"{}\".", source_filename));
+                "ascending"
+            },
+            if sort.directories_first {
+                ", directories first"
+            } else {
+                ""
             }
-        });
+        ));
+    }
+
+    /// Tell the user that the limit of `target` is now `rate` bytes per second.
+    fn report_limit(&self, target: &str, rate: u64) {
+        if rate == 0 {
+            self.user_message
+                .report(&format!("Removed the bandwidth limit of {}.", target));
+        } else {
+            self.user_message.report(&format!(
+                "Limited {} to {}.",
+                target,
+                bitrate_to_string(8 * rate)
+            ));
+        }
+    }
+
+    /// Add `transfer` to the queue, unless its destination already exists and the conflict
+    /// policy says otherwise.
+    ///
+    /// Looking for a conflict asks the host, which would block until a lost connection times
+    /// out, so nothing is added while the session is being replaced.
+    fn enqueue(&mut self, transfer: Transfer) {
+        if !self.connection_state.is_connected() {
+            self.user_message
+                .report("Error: Not connected to the host, please wait.");
+            return;
+        }
+        match dest_exists(&transfer, transfer.get_dest(), &self.sftp) {
+            Ok(false) => self.queue.push(transfer, self.transfer_options),
+            Ok(true) if self.conflict_policy == ConflictPolicy::Ask => {
+                self.conflict_dialog = Some(ConflictDialog::new(transfer));
+            }
+            Ok(true) => self.resolve_conflict(transfer, self.conflict_policy),
+            Err(err) => self.user_message.report(&format!(
+                "Error: Unable to check whether \"{}\" already exists. {}",
+                transfer.get_dest().display(),
+                err
+            )),
+        }
+    }
+
+    /// Add `transfer`, whose destination already exists, to the queue according to `policy`.
+    fn resolve_conflict(&mut self, mut transfer: Transfer, policy: ConflictPolicy) {
+        if !self.connection_state.is_connected() {
+            self.user_message
+                .report("Error: Not connected to the host, please wait.");
+            return;
+        }
+        let mut options = self.transfer_options;
+        match policy {
+            ConflictPolicy::Ask => unreachable!(),
+            ConflictPolicy::Overwrite => options.resume = false,
+            ConflictPolicy::Skip => {
+                self.user_message.report(&format!(
+                    "Skipped \"{}\" because it already exists.",
+                    transfer.source_filename()
+                ));
+                return;
+            }
+            ConflictPolicy::Rename => {
+                let dest = get_unused_path(transfer.get_dest(), |path| {
+                    dest_exists(&transfer, path, &self.sftp)
+                });
+                match dest {
+                    Ok(dest) => transfer.set_dest(dest),
+                    Err(err) => {
+                        self.user_message.report(&format!(
+                            "Error: Unable to find an unused name for \"{}\". {}",
+                            transfer.source_filename(),
+                            err
+                        ));
+                        return;
+                    }
+                }
+            }
+            ConflictPolicy::Resume => options.resume = true,
+            ConflictPolicy::OverwriteIfNewer => match is_source_newer(&transfer, &self.sftp) {
+                Ok(true) => options.resume = false,
+                Ok(false) => {
+                    self.user_message.report(&format!(
+                        "Skipped \"{}\" because the destination is not older.",
+                        transfer.source_filename()
+                    ));
+                    return;
+                }
+                Err(err) => {
+                    self.user_message.report(&format!(
+                        "Error: Unable to compare modification times of \"{}\". {}",
+                        transfer.source_filename(),
+                        err
+                    ));
+                    return;
+                }
+            },
+        }
+        self.queue.push(transfer, options);
+    }
+
+    /// Return everything a transfer thread needs.
+    fn get_transfer_context(&self) -> TransferContext {
+        TransferContext {
+            connection: Arc::clone(&self.connection),
+            shared_session: Arc::clone(&self.shared_session),
+            files: Arc::clone(&self.files),
+            show_hidden_files: Arc::clone(&self.show_hidden_files),
+            user_message: Arc::clone(&self.user_message),
+            retry_policy: self.retry_policy,
+        }
     }
 
     /// Return true if the user has not quit.
@@ -303,19 +1385,103 @@ impl Rftp {
         B: tui::backend::Backend,
     {
         let rect = frame.size();
+        let rect = match &self.input {
+            Some(input) => input.get_prompt().draw(&mut frame, rect),
+            None => rect,
+        };
         let rect = self.user_message.draw(&mut frame, rect);
+        let rect = match self.connection_state.get_status() {
+            Some(status) => draw_connection_status(&status, &mut frame, rect),
+            None => rect,
+        };
 
-        let progress_bars = self.progress_bars.iter().map(|p| p.as_ref()).collect();
+        let progress_bars = self.queue.get_active_progress_bars();
         let rect = Progress::draw_progress_bars(progress_bars, &mut frame, rect);
 
+        let rect = if self.is_queue_focused {
+            self.queue.draw(&mut frame, rect)
+        } else {
+            rect
+        };
+
         self.files.lock().unwrap().draw(&mut frame, rect);
+
+        if let Some(dialog) = &self.conflict_dialog {
+            let rect = frame.size();
+            dialog.draw(&mut frame, rect);
+        }
     }
 }
 
+/// Return the filter with `pattern`, or `None` to show every entry if it is empty.
+fn get_filter(pattern: &str) -> Option<Filter> {
+    if pattern.is_empty() {
+        None
+    } else {
+        Some(Filter::new(pattern))
+    }
+}
+
+/// Draw `status` on the bottom line of `rect` and return the rest of it.
+fn draw_connection_status<B>(status: &str, frame: &mut tui::terminal::Frame<B>, rect: Rect) -> Rect
+where
+    B: tui::backend::Backend,
+{
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .split(rect);
+    let text = [Text::styled(
+        status,
+        Style::default().fg(CONNECTION_LOST_COLOR),
+    )];
+    frame.render_widget(Paragraph::new(text.iter()), chunks[1]);
+    chunks[0]
+}
+
 impl Drop for Rftp {
     fn drop(&mut self) {
+        // The session may already have been lost.
         self.session
             .disconnect(Some(ssh2::DisconnectCode::ByApplication), "", None)
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::buffer_without_style;
+    use tui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+    #[test]
+    fn test_connection_status() {
+        let (_, result) = bounded(1);
+        let state = ConnectionState::Reconnecting { attempt: 2, result };
+        let status = state.get_status().unwrap();
+
+        let mut terminal = Terminal::new(TestBackend::new(45, 3)).unwrap();
+        terminal
+            .draw(|mut frame| {
+                let rect = frame.size();
+                let rect = draw_connection_status(&status, &mut frame, rect);
+                assert_eq!(rect.height, 2);
+            })
             .unwrap();
+        assert_eq!(
+            buffer_without_style(terminal.backend().buffer()),
+            Buffer::with_lines(vec![
+                "                                             ",
+                "                                             ",
+                "Connection lost. Reconnecting (attempt 2)... ",
+            ])
+        );
+
+        let state = ConnectionState::Connected {
+            next_keepalive: Instant::now(),
+            keepalive: None,
+        };
+        assert!(state.is_connected());
+        assert_eq!(state.get_status(), None);
     }
 }
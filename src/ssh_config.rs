@@ -0,0 +1,577 @@
+use crate::known_hosts::StrictHostKeyChecking;
+
+use dirs::home_dir;
+use std::fs::{read_dir, read_to_string};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The max depth of nested `Include` directives, the same as OpenSSH's.
+const MAX_INCLUDE_DEPTH: usize = 16;
+/// The files with the user's known host keys if `UserKnownHostsFile` is not given.
+const DEFAULT_USER_KNOWN_HOSTS_FILES: [&str; 2] = ["~/.ssh/known_hosts", "~/.ssh/known_hosts2"];
+/// The files with the system-wide known host keys if `GlobalKnownHostsFile` is not given.
+const DEFAULT_GLOBAL_KNOWN_HOSTS_FILES: [&str; 2] =
+    ["/etc/ssh/ssh_known_hosts", "/etc/ssh/ssh_known_hosts2"];
+/// The `Match` criteria that are followed by an argument, supported or not.
+const CRITERIA_WITH_ARGUMENT: [&str; 14] = [
+    "address",
+    "command",
+    "exec",
+    "host",
+    "localaddress",
+    "localnetwork",
+    "localport",
+    "localuser",
+    "originalhost",
+    "rdomain",
+    "sessiontype",
+    "tagged",
+    "user",
+    "version",
+];
+
+/// The settings for a single host from an OpenSSH client configuration file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostConfig {
+    /// The real name of the host to connect to.
+    pub host_name: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// The private keys to try, in order.
+    pub identity_files: Vec<PathBuf>,
+    /// The comma-separated jump hosts to connect through, e.g., "user@bastion:2222", or
+    /// "none".
+    pub proxy_jump: Option<String>,
+    /// The command whose standard input and output to connect through, or "none".
+    pub proxy_command: Option<String>,
+    /// The files with the user's known host keys. New keys are added to the first one.
+    pub user_known_hosts_files: Vec<PathBuf>,
+    /// The files with the system-wide known host keys, which are only read.
+    pub global_known_hosts_files: Vec<PathBuf>,
+    /// Whether host names are hashed when new keys are added to the known hosts.
+    pub hash_known_hosts: bool,
+    /// What to do with host keys that are not known, if it was given.
+    pub strict_host_key_checking: Option<StrictHostKeyChecking>,
+}
+
+impl HostConfig {
+    /// Return the settings for `host` from `~/.ssh/config`.
+    ///
+    /// If the file does not exist, the host has no settings apart from its name.
+    pub fn load(host: &str) -> io::Result<Self> {
+        let ssh_dir = match home_dir() {
+            Some(home) => home.join(".ssh"),
+            None => return Ok(HostConfig::new(host)),
+        };
+        let path = ssh_dir.join("config");
+        if path.exists() {
+            HostConfig::from_file(host, &path, &ssh_dir)
+        } else {
+            Ok(HostConfig::new(host))
+        }
+    }
+
+    /// Return the settings for `host` from the file at `path`.
+    ///
+    /// Relative `Include` paths are relative to `ssh_dir`.
+    pub fn from_file(host: &str, path: &Path, ssh_dir: &Path) -> io::Result<Self> {
+        let mut parser = Parser::new(host, ssh_dir);
+        parser.parse(&read_to_string(path)?, 0)?;
+        Ok(parser.finish())
+    }
+
+    fn new(host: &str) -> Self {
+        Parser::new(host, Path::new("")).finish()
+    }
+}
+
+/// Collects the settings for a host from the lines of a configuration file.
+///
+/// As with OpenSSH, the first value of an option that applies to the host is used, except for
+/// `IdentityFile` which may be given several times.
+struct Parser<'a> {
+    original_host: &'a str,
+    ssh_dir: &'a Path,
+    host_name: Option<String>,
+    user: Option<String>,
+    port: Option<u16>,
+    identity_files: Vec<String>,
+    proxy_jump: Option<String>,
+    proxy_command: Option<String>,
+    user_known_hosts_files: Option<Vec<String>>,
+    global_known_hosts_files: Option<Vec<String>>,
+    hash_known_hosts: Option<bool>,
+    strict_host_key_checking: Option<StrictHostKeyChecking>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(original_host: &'a str, ssh_dir: &'a Path) -> Self {
+        Parser {
+            original_host,
+            ssh_dir,
+            host_name: None,
+            user: None,
+            port: None,
+            identity_files: vec![],
+            proxy_jump: None,
+            proxy_command: None,
+            user_known_hosts_files: None,
+            global_known_hosts_files: None,
+            hash_known_hosts: None,
+            strict_host_key_checking: None,
+        }
+    }
+
+    /// Apply the lines of `config` that match the host.
+    fn parse(&mut self, config: &str, depth: usize) -> io::Result<()> {
+        // Lines before the first `Host` or `Match` apply to every host.
+        let mut is_active = true;
+        for (i, line) in config.lines().enumerate() {
+            let invalid_line = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid line {} in ssh config: {}", i + 1, line.trim()),
+                )
+            };
+            let (keyword, args) = match split_line(line) {
+                Some((keyword, args)) => (keyword, args),
+                None => continue,
+            };
+            match keyword.as_str() {
+                "host" => {
+                    is_active =
+                        matches_pattern_list(self.original_host, args.iter().map(String::as_str));
+                }
+                "match" => {
+                    is_active = self.matches_criteria(&args).ok_or_else(invalid_line)?;
+                }
+                _ if !is_active => {}
+                "include" => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "too many nested includes in ssh config",
+                        ));
+                    }
+                    for pattern in args.iter() {
+                        for path in expand_include(&expand_tilde(pattern), self.ssh_dir)? {
+                            self.parse(&read_to_string(path)?, depth + 1)?;
+                        }
+                    }
+                }
+                "hostname" => {
+                    let arg = args.first().ok_or_else(invalid_line)?;
+                    let host_name = arg.replace("%h", self.original_host);
+                    self.host_name.get_or_insert(host_name);
+                }
+                "user" => {
+                    let arg = args.first().ok_or_else(invalid_line)?;
+                    self.user.get_or_insert(arg.clone());
+                }
+                "port" => {
+                    let port = args
+                        .first()
+                        .and_then(|port| port.parse::<u16>().ok())
+                        .ok_or_else(invalid_line)?;
+                    self.port.get_or_insert(port);
+                }
+                "identityfile" => {
+                    let arg = args.first().ok_or_else(invalid_line)?;
+                    self.identity_files.push(arg.clone());
+                }
+                // Like OpenSSH, whichever of `ProxyJump` and `ProxyCommand` comes first is used.
+                "proxyjump" if self.proxy_command.is_none() => {
+                    let arg = args.first().ok_or_else(invalid_line)?;
+                    self.proxy_jump.get_or_insert(arg.clone());
+                }
+                "proxycommand" if self.proxy_jump.is_none() => {
+                    // The command is passed to the shell as it is written, quotes and all.
+                    let (_, command) = split_keyword(line.trim());
+                    if command.is_empty() {
+                        return Err(invalid_line());
+                    }
+                    self.proxy_command.get_or_insert(command.to_string());
+                }
+                "userknownhostsfile" => {
+                    if args.is_empty() {
+                        return Err(invalid_line());
+                    }
+                    self.user_known_hosts_files.get_or_insert(args);
+                }
+                "globalknownhostsfile" => {
+                    if args.is_empty() {
+                        return Err(invalid_line());
+                    }
+                    self.global_known_hosts_files.get_or_insert(args);
+                }
+                "hashknownhosts" => {
+                    let hash_known_hosts = match args.first().map(|arg| arg.to_lowercase()) {
+                        Some(arg) if arg == "yes" => true,
+                        Some(arg) if arg == "no" => false,
+                        _ => return Err(invalid_line()),
+                    };
+                    self.hash_known_hosts.get_or_insert(hash_known_hosts);
+                }
+                "stricthostkeychecking" => {
+                    let mode = args
+                        .first()
+                        .and_then(|mode| mode.parse().ok())
+                        .ok_or_else(invalid_line)?;
+                    self.strict_host_key_checking.get_or_insert(mode);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Return `true` if every criterion of a `Match` line applies to the host, or `None` if
+    /// the criteria cannot be parsed.
+    ///
+    /// Only the `all`, `canonical`, `final`, `host`, `originalhost` and `user` criteria are
+    /// supported. Host names are never canonicalized, so there is a single pass over the file,
+    /// which is the final one. Lines with any other criteria, e.g., `exec`, never match.
+    fn matches_criteria(&self, args: &[String]) -> Option<bool> {
+        let mut args = args.iter();
+        let mut is_match = true;
+        while let Some(criterion) = args.next() {
+            let criterion = criterion.to_lowercase();
+            let (criterion, is_negated) = match criterion.strip_prefix('!') {
+                Some(criterion) => (criterion, true),
+                None => (criterion.as_str(), false),
+            };
+            let matches = match criterion {
+                "all" | "final" => true,
+                "canonical" => false,
+                _ if CRITERIA_WITH_ARGUMENT.contains(&criterion) => {
+                    let patterns = args.next()?.split(',');
+                    match criterion {
+                        "host" => {
+                            let host = self.host_name.as_deref().unwrap_or(self.original_host);
+                            matches_pattern_list(host, patterns)
+                        }
+                        "originalhost" => matches_pattern_list(self.original_host, patterns),
+                        "user" => match &self.user {
+                            Some(user) => matches_pattern_list(user, patterns),
+                            None => false,
+                        },
+                        _ => false,
+                    }
+                }
+                _ => false,
+            };
+            is_match &= matches != is_negated;
+        }
+        Some(is_match)
+    }
+
+    fn finish(self) -> HostConfig {
+        let (original_host, user) = (self.original_host, self.user);
+        let host_name = self.host_name.unwrap_or_else(|| original_host.to_string());
+        let identity_files = self
+            .identity_files
+            .iter()
+            .map(|path| {
+                let path = path
+                    .replace("%h", &host_name)
+                    .replace("%r", user.as_deref().unwrap_or(""));
+                expand_tilde(&path)
+            })
+            .collect();
+        // "none" means that no files are used.
+        let known_hosts_files = |files: Option<Vec<String>>, default: &[&str]| -> Vec<PathBuf> {
+            match files {
+                Some(files) => files
+                    .iter()
+                    .filter(|path| !path.eq_ignore_ascii_case("none"))
+                    .map(|path| expand_tilde(path))
+                    .collect(),
+                None => default.iter().map(|path| expand_tilde(path)).collect(),
+            }
+        };
+        HostConfig {
+            host_name,
+            user,
+            port: self.port,
+            identity_files,
+            proxy_jump: self.proxy_jump,
+            proxy_command: self.proxy_command,
+            user_known_hosts_files: known_hosts_files(
+                self.user_known_hosts_files,
+                &DEFAULT_USER_KNOWN_HOSTS_FILES,
+            ),
+            global_known_hosts_files: known_hosts_files(
+                self.global_known_hosts_files,
+                &DEFAULT_GLOBAL_KNOWN_HOSTS_FILES,
+            ),
+            hash_known_hosts: self.hash_known_hosts.unwrap_or(false),
+            strict_host_key_checking: self.strict_host_key_checking,
+        }
+    }
+}
+
+/// Split a line into its lowercase keyword and arguments, or return `None` if it is empty or
+/// a comment.
+///
+/// The keyword can be separated from the arguments by whitespace or `=`, and arguments can be
+/// quoted to contain whitespace.
+fn split_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (keyword, rest) = split_keyword(line);
+
+    let mut args = vec![];
+    let mut chars = rest.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let arg: String = match chars.peek() {
+            None => break,
+            Some('"') => {
+                chars.next();
+                chars.by_ref().take_while(|&c| c != '"').collect()
+            }
+            Some(_) => chars.by_ref().take_while(|c| !c.is_whitespace()).collect(),
+        };
+        args.push(arg);
+    }
+    Some((keyword.to_lowercase(), args))
+}
+
+/// Split a trimmed line into its keyword and the rest of the line.
+fn split_keyword(line: &str) -> (&str, &str) {
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let (keyword, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest).trim_start();
+    (keyword, rest)
+}
+
+/// Return `true` if `name` matches any pattern in `patterns` and none of the negated ones.
+pub fn matches_pattern_list<'a>(name: &str, patterns: impl Iterator<Item = &'a str>) -> bool {
+    let name = name.to_lowercase();
+    let mut is_match = false;
+    for pattern in patterns {
+        let pattern = pattern.to_lowercase();
+        match pattern.strip_prefix('!') {
+            Some(pattern) if matches_wildcard(&name, pattern) => return false,
+            Some(_) => {}
+            None => is_match |= matches_wildcard(&name, &pattern),
+        }
+    }
+    is_match
+}
+
+/// Return `true` if `name` matches `pattern`, where `*` matches any number of characters and
+/// `?` matches exactly one.
+pub fn matches_wildcard(name: &str, pattern: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    // The positions to go back to when a mismatch follows a `*`.
+    let (mut star, mut star_name) = (None, 0);
+    let (mut i, mut j) = (0, 0);
+    while i < name.len() {
+        if j < pattern.len() && (pattern[j] == '?' || pattern[j] == name[i]) {
+            i += 1;
+            j += 1;
+        } else if j < pattern.len() && pattern[j] == '*' {
+            star = Some(j);
+            star_name = i;
+            j += 1;
+        } else if let Some(star) = star {
+            star_name += 1;
+            i = star_name;
+            j = star + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[j..].iter().all(|&c| c == '*')
+}
+
+/// Replace a leading `~` in `path` with the home directory.
+fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Return the files that match the `Include` path `pattern`, which may contain wildcards in
+/// its file name, in lexical order.
+fn expand_include(pattern: &Path, ssh_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let pattern = ssh_dir.join(pattern);
+    let file_name = match pattern.file_name().and_then(|name| name.to_str()) {
+        Some(file_name) if file_name.contains(['*', '?']) => file_name,
+        _ => {
+            return Ok(if pattern.is_file() {
+                vec![pattern]
+            } else {
+                vec![]
+            })
+        }
+    };
+    let dir = pattern.parent().unwrap_or(ssh_dir);
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut paths = vec![];
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        let is_match = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| matches_wildcard(name, file_name));
+        if is_match && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    #[test]
+    fn test_wildcard() {
+        assert!(matches_wildcard("web1.example.com", "*.example.com"));
+        assert!(matches_wildcard("web1", "web?"));
+        assert!(matches_wildcard("abc", "a*b*c"));
+        assert!(!matches_wildcard("web12", "web?"));
+        assert!(!matches_wildcard("example.org", "*.example.com"));
+        assert!(matches_pattern_list("db", ["*", "!web*"].iter().copied()));
+        assert!(!matches_pattern_list("web", ["*", "!web*"].iter().copied()));
+    }
+
+    #[test]
+    fn test_host_config() {
+        let ssh_dir = std::env::temp_dir().join(format!("rftp-ssh-config-{}", std::process::id()));
+        create_dir_all(ssh_dir.join("config.d")).unwrap();
+        write(
+            ssh_dir.join("config.d/work"),
+            "Host build\n  HostName build.internal\n  Port 2200\n",
+        )
+        .unwrap();
+        write(
+            ssh_dir.join("config"),
+            r#"
+# Comments and blank lines are ignored.
+Include config.d/*
+
+Host web? !web9
+    HostName %h.example.com
+    ProxyCommand nc -X connect -x "proxy:8080" %h %p
+    ProxyJump ignored
+    User = deploy
+    IdentityFile "/keys/my key"
+
+Match host *.internal
+    User builder
+    StrictHostKeyChecking accept-new
+    ProxyJump admin@bastion:2222,gateway
+
+Host *
+    User nobody
+    Port 22
+    IdentityFile /keys/%r@%h
+    UserKnownHostsFile /hosts/known_hosts "/hosts/more hosts"
+    HashKnownHosts yes
+"#,
+        )
+        .unwrap();
+        let load = |host| HostConfig::from_file(host, &ssh_dir.join("config"), &ssh_dir).unwrap();
+
+        assert_eq!(
+            load("web1"),
+            HostConfig {
+                host_name: "web1.example.com".into(),
+                user: Some("deploy".into()),
+                port: Some(22),
+                identity_files: vec![
+                    PathBuf::from("/keys/my key"),
+                    PathBuf::from("/keys/deploy@web1.example.com"),
+                ],
+                proxy_jump: None,
+                proxy_command: Some(r#"nc -X connect -x "proxy:8080" %h %p"#.into()),
+                user_known_hosts_files: vec![
+                    PathBuf::from("/hosts/known_hosts"),
+                    PathBuf::from("/hosts/more hosts"),
+                ],
+                global_known_hosts_files: vec![
+                    PathBuf::from("/etc/ssh/ssh_known_hosts"),
+                    PathBuf::from("/etc/ssh/ssh_known_hosts2"),
+                ],
+                hash_known_hosts: true,
+                strict_host_key_checking: None,
+            }
+        );
+        assert_eq!(
+            load("build"),
+            HostConfig {
+                host_name: "build.internal".into(),
+                user: Some("builder".into()),
+                port: Some(2200),
+                identity_files: vec![PathBuf::from("/keys/builder@build.internal")],
+                proxy_jump: Some("admin@bastion:2222,gateway".into()),
+                proxy_command: None,
+                user_known_hosts_files: vec![
+                    PathBuf::from("/hosts/known_hosts"),
+                    PathBuf::from("/hosts/more hosts"),
+                ],
+                global_known_hosts_files: vec![
+                    PathBuf::from("/etc/ssh/ssh_known_hosts"),
+                    PathBuf::from("/etc/ssh/ssh_known_hosts2"),
+                ],
+                hash_known_hosts: true,
+                strict_host_key_checking: Some(StrictHostKeyChecking::AcceptNew),
+            }
+        );
+        assert_eq!(load("web9").host_name, "web9");
+        assert_eq!(load("web9").user.as_deref(), Some("nobody"));
+        remove_dir_all(&ssh_dir).unwrap();
+    }
+
+    #[test]
+    fn test_match_criteria() {
+        let ssh_dir = std::env::temp_dir().join(format!("rftp-ssh-match-{}", std::process::id()));
+        create_dir_all(&ssh_dir).unwrap();
+        write(
+            ssh_dir.join("config"),
+            r#"
+Match canonical host web1
+    User canonical
+
+Match final host web1
+    Port 2200
+
+Match exec "test -f /etc/hosts" host web1
+    User exec
+
+Match unknown
+    User unknown
+
+Match localnetwork 10.0.0.0/8,192.168.0.0/16 !host web1
+    User local
+
+Match all
+    User nobody
+"#,
+        )
+        .unwrap();
+        let load = |host| HostConfig::from_file(host, &ssh_dir.join("config"), &ssh_dir).unwrap();
+
+        assert_eq!(load("web1").user.as_deref(), Some("nobody"));
+        assert_eq!(load("web1").port, Some(2200));
+        assert_eq!(load("web2").user.as_deref(), Some("nobody"));
+        assert_eq!(load("web2").port, None);
+        remove_dir_all(&ssh_dir).unwrap();
+    }
+}
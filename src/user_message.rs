@@ -1,7 +1,6 @@
 use std::collections::VecDeque;
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
-use textwrap;
 use tui::{
     layout::{Constraint, Direction, Layout},
     widgets::{Paragraph, Text},
@@ -1,11 +1,25 @@
 use std::error::Error;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tui::{buffer::Buffer, style::Style};
 
 /// Return the path to the host home directory.
-pub fn get_remote_home_dir(session: &ssh2::Session) -> Result<PathBuf, Box<dyn Error>> {
+///
+/// The SFTP server starts in the home directory, so this is asked of the server first. Running
+/// `pwd` is only tried if that fails, because SFTP-only hosts do not allow running commands.
+pub fn get_remote_home_dir(
+    session: &ssh2::Session,
+    sftp: &ssh2::Sftp,
+) -> Result<PathBuf, Box<dyn Error>> {
+    match sftp.realpath(Path::new(".")) {
+        Ok(path) => Ok(path),
+        Err(_) => get_remote_working_dir(session),
+    }
+}
+
+/// Return the working directory of a command run by the host.
+fn get_remote_working_dir(session: &ssh2::Session) -> Result<PathBuf, Box<dyn Error>> {
     let mut channel = session.channel_session()?;
     channel.exec("pwd")?;
     let mut result = String::new();
@@ -35,6 +49,31 @@ pub fn duration_to_string(t: Duration) -> String {
     }
 }
 
+/// Returns a `String` that represents a time in seconds since the Unix epoch as a UTC date
+/// and time, e.g., `2020-05-17 14:03`.
+pub fn time_to_string(seconds: u64) -> String {
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+    let (hours, minutes) = (seconds / 3_600, seconds % 3_600 / 60);
+    // Convert the days to a date in the proleptic Gregorian calendar, using eras of 400 years
+    // that start on March 1st so that leap days come last.
+    let days = days + 719_468;
+    let (era, day_of_era) = (days / 146_097, days % 146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let (year, month) = if month < 10 {
+        (era * 400 + year_of_era, month + 3)
+    } else {
+        (era * 400 + year_of_era + 1, month - 9)
+    };
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        year, month, day, hours, minutes
+    )
+}
+
 /// Returns a `String` that represents a bitrate.
 pub fn bitrate_to_string(rate: u64) -> String {
     if rate < 1_000 {
@@ -64,7 +103,7 @@ pub fn bytes_to_string(num_bytes: u64) -> String {
 /// Return a `Buffer` that is the same, but with the default style.
 pub fn buffer_without_style(buffer: &Buffer) -> Buffer {
     let mut buffer = buffer.clone();
-    let rect = buffer.area().clone();
+    let rect = *buffer.area();
     for x in rect.x..rect.width {
         for y in rect.y..rect.height {
             buffer.get_mut(x, y).set_style(Style::default());
@@ -93,6 +132,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_time() {
+        assert_eq!(time_to_string(0), "1970-01-01 00:00".to_string());
+        assert_eq!(time_to_string(951_782_400), "2000-02-29 00:00".to_string());
+        assert_eq!(
+            time_to_string(1_589_724_180),
+            "2020-05-17 14:03".to_string()
+        );
+        assert_eq!(
+            time_to_string(4_107_542_399),
+            "2100-02-28 23:59".to_string()
+        );
+    }
+
     #[test]
     fn test_bitrate() {
         assert_eq!(bitrate_to_string(4), "4 bit/s".to_string());